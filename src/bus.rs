@@ -1,57 +1,369 @@
-use std::fs;
+use std::cell::Cell;
+use std::fmt;
 
 use crate::constants::*;
+use crate::memory_map::*;
+use crate::rom::*;
+
+// What to do when the CPU tries to write to ROM. The real hardware simply
+// ignores these writes, but they usually point to an emulation bug
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum RomWritePolicy {
+    Ignore,
+    Log,
+    // Stops the emulator once the instruction has finished
+    Trap,
+}
+
+// What a watchpoint looks out for. A value watchpoint is a write of one
+// particular value
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Value(Byte),
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Watchpoint {
+    pub addr: Word,
+    pub kind: WatchKind,
+}
+
+// An access that set off a watchpoint, at the address the CPU actually used
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WatchHit {
+    pub addr: Word,
+    pub kind: WatchKind,
+    pub data: Byte,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            WatchKind::Read => write!(f, "read of {:02X} from {:04X}", self.data, self.addr),
+            _ => write!(f, "write of {:02X} to {:04X}", self.data, self.addr),
+        }
+    }
+}
+
+// A write to ROM caught by the Trap policy, at the address the CPU used
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct RomWrite {
+    pub addr: Word,
+    pub data: Byte,
+}
+
+impl fmt::Display for RomWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "write of {:02X} to ROM address {:04X}", self.data, self.addr)
+    }
+}
+
+// Index used in the region lookup for addresses no region covers
+const NO_REGION: u8 = u8::MAX;
 
 pub struct Bus {
-    memory: [Byte; MEMORY_SIZE]
+    memory: [Byte; MEMORY_SIZE],
+
+    // The memory map of the board, along with which region each address
+    // belongs to so that accesses don't need to search the map
+    regions: Vec<MemoryRegion>,
+    region_lookup: Vec<u8>,
+
+    rom_write_policy: RomWritePolicy,
+    rom_write: Option<RomWrite>,
+
+    // Reads only borrow the bus, so the first watchpoint hit is latched in a
+    // Cell until the emulator collects it. With no watchpoints set an access
+    // costs a single length check
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Cell<Option<WatchHit>>,
 }
 
 impl Bus {
 
     pub fn new() -> Bus {
+        Bus::with_memory_map(&FLAT_MEMORY_MAP, RomWritePolicy::Ignore)
+    }
+
+    pub fn with_memory_map(regions: &[MemoryRegion], rom_write_policy: RomWritePolicy) -> Bus {
+        // Regions later in the map take precedence where they overlap
+        let mut region_lookup = vec![NO_REGION; MEMORY_SIZE];
+
+        for (index, region) in regions.iter().enumerate() {
+            if region.start > region.end || index >= NO_REGION as usize {
+                panic!("Invalid memory region {:04X}-{:04X}", region.start, region.end);
+            }
+
+            region_lookup[region.start as usize..=region.end as usize].fill(index as u8);
+        }
+
         Bus {
-            memory: [0; MEMORY_SIZE]
+            memory: [0; MEMORY_SIZE],
+            regions: regions.to_vec(),
+            region_lookup: region_lookup,
+            rom_write_policy: rom_write_policy,
+            rom_write: None,
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+        }
+    }
+
+    pub fn load_rom(&mut self, rom_set: &RomSet) {
+        for (chip, data) in &rom_set.chips {
+            let start = chip.addr as usize;
+            self.memory[start..start + data.len()].copy_from_slice(data);
+        }
+    }
+
+    pub fn read_byte(&self, addr: Word) -> Byte {
+        let data = self.peek(addr);
+
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, false, data);
+        }
+
+        data
+    }
+
+    // Reads without setting off watchpoints, for anything looking at memory
+    // other than the CPU itself
+    pub fn peek(&self, addr: Word) -> Byte {
+        let (addr, kind) = self.resolve(addr);
+
+        match kind {
+            RegionKind::Unmapped { open_bus } => open_bus,
+            _ => self.memory[addr as usize],
+        }
+    }
+
+    pub fn write_byte(&mut self, addr: Word, data: Byte) {
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, true, data);
+        }
+
+        let (target, kind) = self.resolve(addr);
+
+        match kind {
+            RegionKind::Ram | RegionKind::Vram => self.memory[target as usize] = data,
+            RegionKind::Rom => match self.rom_write_policy {
+                RomWritePolicy::Ignore => (),
+                RomWritePolicy::Log => eprintln!("Ignored write of {:02X} to ROM address {:04X}", data, addr),
+                RomWritePolicy::Trap => {
+                    // Like a watchpoint, only the first write is kept until
+                    // the emulator collects it
+                    if self.rom_write.is_none() {
+                        self.rom_write = Some(RomWrite { addr: addr, data: data });
+                    }
+                },
+            },
+            _ => (),
+        };
+    }
+
+    // Writes straight to memory the way a debugger would patch it, ignoring
+    // ROM protection. Unmapped addresses have nothing to write to
+    pub fn poke(&mut self, addr: Word, data: Byte) {
+        let (addr, kind) = self.resolve(addr);
+
+        if !matches!(kind, RegionKind::Unmapped { .. }) {
+            self.memory[addr as usize] = data;
+        }
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
         }
     }
 
-    pub fn load_rom(&mut self) {
-        // We will hardcode file names (at least for now) as the
-        // emulated hardware is specific to Space Invaders
-        // According to documentation, the 4 rom files should be loaded
-        // into memory as follows:
-        //   invaders.h 0000-07FF
-        //   invaders.g 0800-0FFF
-        //   invaders.f 1000-17FF
-        //   invaders.e 1800-1FFF
+    // Removes every watchpoint on addr, returning whether there were any
+    pub fn remove_watchpoints(&mut self, addr: Word) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.addr != addr);
+        self.watchpoints.len() != count
+    }
 
-        let invaders_h = fs::read("rom/invaders.h")
-            .expect("Something went wrong reading the file");
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
 
-        let invaders_g = fs::read("rom/invaders.g")
-            .expect("Something went wrong reading the file");
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
 
-        let invaders_f = fs::read("rom/invaders.f")
-            .expect("Something went wrong reading the file");
+    pub fn take_rom_write(&mut self) -> Option<RomWrite> {
+        self.rom_write.take()
+    }
 
-        let invaders_e = fs::read("rom/invaders.e")
-            .expect("Something went wrong reading the file");
+    fn check_watchpoints(&self, addr: Word, write: bool, data: Byte) {
+        // Watching an address also catches accesses through its mirrors
+        let (target, _) = self.resolve(addr);
 
-        for i in 0x0000..0x2000 {
-            match i {
-                0x0000..=0x07FF => self.memory[i] = invaders_h[i],
-                0x0800..=0x0FFF => self.memory[i] = invaders_h[i - 0x0800],
-                0x1000..=0x17FF => self.memory[i] = invaders_h[i - 0x1000],
-                0x1800..=0x1FFF => self.memory[i] = invaders_h[i - 0x1800],
-                _ => println!("Tried to load bad ROM address {:04X}", i)
+        for watchpoint in &self.watchpoints {
+            let triggered = match watchpoint.kind {
+                WatchKind::Read => !write,
+                WatchKind::Write => write,
+                WatchKind::Value(value) => write && value == data,
             };
+
+            if triggered && self.watch_hit.get().is_none() && self.resolve(watchpoint.addr).0 == target {
+                self.watch_hit.set(Some(WatchHit { addr: addr, kind: watchpoint.kind, data: data }));
+            }
         }
     }
 
-    pub fn read_byte(&self, addr: Word) -> Byte {
-        self.memory[addr as usize]
+    fn region_kind(&self, addr: Word) -> RegionKind {
+        match self.region_lookup[addr as usize] {
+            NO_REGION => RegionKind::Unmapped { open_bus: 0 },
+            index => self.regions[index as usize].kind,
+        }
     }
 
-    pub fn write_byte(&mut self, addr: Word, data: Byte) {
-        self.memory[addr as usize] = data;
+    fn resolve(&self, addr: Word) -> (Word, RegionKind) {
+        // Follow mirrors through to the region that actually backs the address.
+        // A mirror pointing back into itself would never resolve, so only
+        // follow as many as there are regions
+        let mut addr = addr;
+
+        for _ in 0..=self.regions.len() {
+            match self.region_kind(addr) {
+                RegionKind::Mirror { base, mask } => addr = base | (addr & mask),
+                kind => return (addr, kind),
+            }
+        }
+
+        panic!("Memory map mirrors address {:04X} back onto itself", addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::*;
+
+    #[test]
+    fn test_rom_is_read_only() {
+        let mut bus = Bus::with_memory_map(&BOARD_MEMORY_MAP, RomWritePolicy::Ignore);
+        bus.memory[0x1234] = 0xAB;
+
+        bus.write_byte(0x1234, 0xCD);
+        assert_eq!(bus.read_byte(0x1234), 0xAB);
+
+        bus.write_byte(0x2000, 0xCD);
+        assert_eq!(bus.read_byte(0x2000), 0xCD);
+    }
+
+    #[test]
+    fn test_poke() {
+        let mut bus = Bus::with_memory_map(&BOARD_MEMORY_MAP, RomWritePolicy::Trap);
+
+        bus.poke(0x1234, 0xCD);
+        bus.poke(0x6010, 0x12);
+
+        assert_eq!(bus.read_byte(0x1234), 0xCD);
+        assert_eq!(bus.read_byte(0x2010), 0x12);
+    }
+
+    #[test]
+    fn test_rom_write_trap() {
+        let mut bus = Bus::with_memory_map(&BOARD_MEMORY_MAP, RomWritePolicy::Trap);
+
+        bus.write_byte(0x8010, 0xCD);
+        bus.write_byte(0x0020, 0xEF);
+        assert_eq!(bus.read_byte(0x0010), 0x00);
+
+        assert_eq!(bus.take_rom_write(), Some(RomWrite { addr: 0x8010, data: 0xCD }));
+        assert_eq!(bus.take_rom_write(), None);
+
+        bus.write_byte(0x2000, 0x12);
+        assert_eq!(bus.take_rom_write(), None);
+    }
+
+    #[test]
+    fn test_ram_mirror() {
+        let mut bus = Bus::with_memory_map(&BOARD_MEMORY_MAP, RomWritePolicy::Ignore);
+
+        bus.write_byte(0x6010, 0x12);
+        assert_eq!(bus.read_byte(0x2010), 0x12);
+        assert_eq!(bus.read_byte(0xE010), 0x12);
+
+        bus.write_byte(0x7FFF, 0x34);
+        assert_eq!(bus.read_byte(0x3FFF), 0x34);
+
+        // A15 isn't decoded, so 0x8000 up repeats ROM before RAM. Neither
+        // that nor the ROM space at 0x4000 reaches RAM
+        bus.write_byte(0x4010, 0x56);
+        bus.write_byte(0x8010, 0x78);
+        assert_eq!(bus.read_byte(0x2010), 0x12);
+        assert_eq!(bus.read_byte(0x4010), 0x00);
+        assert_eq!(bus.read_byte(0x8010), 0x00);
+    }
+
+    #[test]
+    fn test_rom_mirror() {
+        let mut bus = Bus::with_memory_map(&BOARD_MEMORY_MAP, RomWritePolicy::Ignore);
+        bus.memory[0x4010] = 0xAB;
+
+        // The extra ROM is mirrored above 0x8000 along with everything else
+        bus.write_byte(0xC010, 0x34);
+        assert_eq!(bus.read_byte(0xC010), 0xAB);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut bus = Bus::with_memory_map(&BOARD_MEMORY_MAP, RomWritePolicy::Ignore);
+        bus.add_watchpoint(Watchpoint { addr: 0x20F8, kind: WatchKind::Write });
+        bus.add_watchpoint(Watchpoint { addr: 0x2100, kind: WatchKind::Read });
+        bus.add_watchpoint(Watchpoint { addr: 0x2200, kind: WatchKind::Value(0x05) });
+
+        bus.write_byte(0x2100, 0x12);
+        bus.write_byte(0x2200, 0x04);
+        assert_eq!(bus.peek(0x2100), 0x12);
+        assert_eq!(bus.take_watch_hit(), None);
+
+        // Writes through a mirror are caught too
+        bus.write_byte(0x60F8, 0x34);
+        assert_eq!(bus.take_watch_hit(), Some(WatchHit { addr: 0x60F8, kind: WatchKind::Write, data: 0x34 }));
+        assert_eq!(bus.take_watch_hit(), None);
+
+        bus.read_byte(0x2100);
+        assert_eq!(bus.take_watch_hit().unwrap().to_string(), "read of 12 from 2100");
+
+        bus.write_byte(0x2200, 0x05);
+        assert_eq!(bus.take_watch_hit().unwrap().kind, WatchKind::Value(0x05));
+
+        assert!(bus.remove_watchpoints(0x2200));
+        assert!(!bus.remove_watchpoints(0x2200));
+        assert_eq!(bus.watchpoints().len(), 2);
+    }
+
+    #[test]
+    fn test_unmapped() {
+        let regions = [
+            MemoryRegion::ram(0x0000, 0x0FFF),
+            MemoryRegion::unmapped(0x1000, 0x1FFF, 0xFF),
+        ];
+        let mut bus = Bus::with_memory_map(&regions, RomWritePolicy::Trap);
+
+        bus.write_byte(0x1000, 0x12);
+        assert_eq!(bus.read_byte(0x1000), 0xFF);
+
+        // Addresses outside of every region are unmapped too
+        bus.write_byte(0x2000, 0x12);
+        assert_eq!(bus.read_byte(0x2000), 0x00);
+    }
+
+    #[test]
+    fn test_flat_memory() {
+        let mut bus = Bus::new();
+
+        bus.write_byte(0x1234, 0xCD);
+        bus.write_byte(0x4010, 0x12);
+
+        assert_eq!(bus.read_byte(0x1234), 0xCD);
+        assert_eq!(bus.read_byte(0x4010), 0x12);
+        assert_eq!(bus.read_byte(0x2010), 0x00);
     }
 }
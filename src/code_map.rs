@@ -0,0 +1,286 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bus::*;
+use crate::constants::*;
+use crate::disassembler::*;
+use crate::ops::*;
+
+// Where the CPU starts executing on this board: the reset vector and the two
+// video interrupts
+pub const BOARD_ENTRY_POINTS: [(Word, &str); 3] = [
+    (0x0000, "RESET"),
+    ((MID_SCREEN_INTERRUPT as Word) * 8, "MID_SCREEN"),
+    ((VBLANK_INTERRUPT as Word) * 8, "VBLANK"),
+];
+
+// Bytes written per DB line in the listing
+const DATA_BYTES_PER_LINE: usize = 8;
+
+// Works out which bytes of ROM are code by following the program from its
+// entry points, the way the CPU would. A linear sweep decodes data tables as
+// if they were instructions, whereas here anything that is never reached is
+// treated as data. Jumps and calls are followed, and calls are assumed to
+// return. Computed jumps (PCHL) can't be followed, so code only reached
+// through them shows up as data
+pub struct CodeMap {
+    ranges: Vec<(Word, Word)>,
+    instructions: BTreeMap<Word, Instruction>,
+    labels: BTreeMap<Word, String>,
+}
+
+impl CodeMap {
+
+    // ranges are the inclusive start and end of each stretch of ROM
+    pub fn analyse(bus: &Bus, ranges: &[(Word, Word)], entry_points: &[(Word, &str)]) -> CodeMap {
+        let mut code_map = CodeMap {
+            ranges: ranges.to_vec(),
+            instructions: BTreeMap::new(),
+            labels: BTreeMap::new(),
+        };
+
+        let mut pending: Vec<Word> = Vec::new();
+
+        for (addr, name) in entry_points {
+            if code_map.in_rom(*addr) {
+                code_map.labels.insert(*addr, name.to_string());
+                pending.push(*addr);
+            }
+        }
+
+        while let Some(start) = pending.pop() {
+            let mut addr = start;
+
+            // Decode until the flow of control leaves this stretch of code
+            while code_map.in_rom(addr) && !code_map.instructions.contains_key(&addr) {
+                let instruction = disassemble(bus, addr);
+
+                let operation = match instruction.opcode {
+                    Some(opcode) if code_map.in_rom(instruction.next_addr().wrapping_sub(1)) => opcode.operation,
+                    _ => break,
+                };
+
+                let target = match operation {
+                    Operation::RST => Some((instruction.bytes[0] & 0x38) as Word),
+                    _ if is_branch(operation) => instruction.operand,
+                    _ => None,
+                };
+
+                if let Some(target) = target.filter(|target| code_map.in_rom(*target)) {
+                    if operation != Operation::RST {
+                        code_map.labels.entry(target).or_insert(format!("L{:04X}", target));
+                    }
+                    pending.push(target);
+                }
+
+                addr = instruction.next_addr();
+                code_map.instructions.insert(instruction.addr, instruction);
+
+                if matches!(operation, Operation::JMP | Operation::RET | Operation::PCHL) {
+                    break;
+                }
+            }
+        }
+
+        code_map
+    }
+
+    pub fn is_code(&self, addr: Word) -> bool {
+        self.instructions.range(..=addr).next_back()
+            .is_some_and(|(_, instruction)| addr.wrapping_sub(instruction.addr) < instruction.bytes.len() as Word)
+    }
+
+    pub fn label(&self, addr: Word) -> Option<&str> {
+        self.labels.get(&addr).map(|label| label.as_str())
+    }
+
+    // A listing that assembles back to the same bytes. Labels are only used
+    // where they land on the start of a line, as a branch into the middle of
+    // an instruction can't be written any other way than as an address
+    pub fn to_asm(&self, bus: &Bus, title: &str) -> String {
+        let lines = self.lines(bus);
+        let placed: BTreeSet<Word> = lines.iter().map(|(addr, _)| *addr).collect();
+
+        let label_for = |addr: Word| self.labels.get(&addr).filter(|_| placed.contains(&addr));
+
+        let mut asm = format!("; {}\n; Code was found by following the program from its entry points,\n; anything never reached is listed as data\n", title);
+        let mut next_addr = None;
+
+        for (addr, line) in lines {
+            if next_addr != Some(addr) {
+                asm.push_str(&format!("\n        ORG {}\n", format_hex(addr, 4)));
+            }
+
+            if let Some(label) = label_for(addr) {
+                asm.push_str(&format!("{}:\n", label));
+            }
+
+            let (text, len) = match line {
+                Line::Code(instruction) => {
+                    let text = instruction.text_with_operand(|operand, digits| match label_for(operand) {
+                        Some(label) if digits == 4 => label.clone(),
+                        _ => format_hex(operand, digits),
+                    });
+
+                    (text, instruction.bytes.len())
+                },
+                Line::Data(bytes) => {
+                    let values: Vec<String> = bytes.iter().map(|byte| format_hex(*byte as Word, 2)).collect();
+                    (format!("DB {}", values.join(",")), bytes.len())
+                },
+            };
+
+            asm.push_str(&format!("        {:<34} ; {:04X}\n", text, addr));
+            next_addr = Some(addr.wrapping_add(len as Word));
+        }
+
+        asm
+    }
+
+    // Splits the ROM into instructions and runs of data, breaking the data
+    // wherever a label or instruction starts
+    fn lines(&self, bus: &Bus) -> Vec<(Word, Line<'_>)> {
+        let mut lines = Vec::new();
+
+        for &(start, end) in &self.ranges {
+            let mut addr = start as usize;
+            let mut data: Vec<Byte> = Vec::new();
+            let mut data_start = start;
+
+            while addr <= end as usize {
+                let instruction = self.instructions.get(&(addr as Word));
+                let breaks_data = instruction.is_some() || self.labels.contains_key(&(addr as Word)) || data.len() == DATA_BYTES_PER_LINE;
+
+                if breaks_data && !data.is_empty() {
+                    lines.push((data_start, Line::Data(std::mem::take(&mut data))));
+                }
+
+                match instruction {
+                    Some(instruction) => {
+                        lines.push((addr as Word, Line::Code(instruction)));
+                        addr += instruction.bytes.len();
+                    },
+                    None => {
+                        if data.is_empty() {
+                            data_start = addr as Word;
+                        }
+                        data.push(bus.peek(addr as Word));
+                        addr += 1;
+                    },
+                };
+            }
+
+            if !data.is_empty() {
+                lines.push((data_start, Line::Data(data)));
+            }
+        }
+
+        lines
+    }
+
+    fn in_rom(&self, addr: Word) -> bool {
+        self.ranges.iter().any(|&(start, end)| addr >= start && addr <= end)
+    }
+}
+
+enum Line<'a> {
+    Code(&'a Instruction),
+    Data(Vec<Byte>),
+}
+
+// Jumps and calls, which all take their target as the operand
+fn is_branch(operation: Operation) -> bool {
+    matches!(operation,
+        Operation::JMP | Operation::JNZ | Operation::JZ | Operation::JNC | Operation::JC |
+        Operation::JPO | Operation::JPE | Operation::JP | Operation::JM |
+        Operation::CALL | Operation::CNZ | Operation::CZ | Operation::CNC | Operation::CC |
+        Operation::CPO | Operation::CPE | Operation::CP | Operation::CM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: [Byte; 32] = [
+        0xC3, 0x10, 0x00,                   // 0000  JMP 0010H
+        0x48, 0x49, 0x00, 0x00, 0x00,       // 0003  Data
+        0xC9,                               // 0008  RET
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xCD, 0x18, 0x00,                   // 0010  CALL 0018H
+        0xC2, 0x10, 0x00,                   // 0013  JNZ 0010H
+        0x76,                               // 0016  HLT
+        0xC9,                               // 0017  RET
+        0x3E, 0x05,                         // 0018  MVI A,05H
+        0xC9,                               // 001A  RET
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn test_bus() -> Bus {
+        let mut bus = Bus::new();
+        for (addr, byte) in PROGRAM.iter().enumerate() {
+            bus.write_byte(addr as Word, *byte);
+        }
+        bus
+    }
+
+    #[test]
+    fn test_analyse() {
+        let code_map = CodeMap::analyse(&test_bus(), &[(0x0000, 0x001F)], &BOARD_ENTRY_POINTS);
+
+        assert!(code_map.is_code(0x0000));
+        assert!(code_map.is_code(0x0002));
+        assert!(!code_map.is_code(0x0003));
+        assert!(code_map.is_code(0x0008));
+        assert!(!code_map.is_code(0x0009));
+        assert!(code_map.is_code(0x0019));
+        assert!(!code_map.is_code(0x001B));
+
+        assert_eq!(code_map.label(0x0010), Some("VBLANK"));
+        assert_eq!(code_map.label(0x0018), Some("L0018"));
+        assert_eq!(code_map.label(0x0013), None);
+    }
+
+    #[test]
+    fn test_to_asm() {
+        let bus = test_bus();
+        let code_map = CodeMap::analyse(&bus, &[(0x0000, 0x001F)], &BOARD_ENTRY_POINTS);
+
+        assert_eq!(code_map.to_asm(&bus, "Test"), "\
+; Test
+; Code was found by following the program from its entry points,
+; anything never reached is listed as data
+
+        ORG 0000H
+RESET:
+        JMP VBLANK                         ; 0000
+        DB 48H,49H,00H,00H,00H             ; 0003
+MID_SCREEN:
+        RET                                ; 0008
+        DB 00H,00H,00H,00H,00H,00H,00H     ; 0009
+VBLANK:
+        CALL L0018                         ; 0010
+        JNZ VBLANK                         ; 0013
+        HLT                                ; 0016
+        RET                                ; 0017
+L0018:
+        MVI A,05H                          ; 0018
+        RET                                ; 001A
+        DB 00H,00H,00H,00H,00H             ; 001B
+");
+    }
+
+    #[test]
+    fn test_branch_into_instruction() {
+        // JMP 0001H lands on the operand of the MVI, so the label can't be placed
+        let mut bus = Bus::new();
+        for (addr, byte) in [0x3E, 0xC9, 0xC3, 0x01, 0x00].iter().enumerate() {
+            bus.write_byte(addr as Word, *byte);
+        }
+
+        let code_map = CodeMap::analyse(&bus, &[(0x0000, 0x0004)], &[(0x0000, "START")]);
+        let asm = code_map.to_asm(&bus, "Test");
+
+        assert!(asm.contains("MVI A,0C9H"));
+        assert!(asm.contains("JMP 0001H"));
+        assert!(code_map.is_code(0x0001));
+    }
+}
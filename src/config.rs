@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use crate::bus::*;
+use crate::constants::*;
+use crate::debugger::*;
+use crate::dip_switches::*;
+use crate::overlay::*;
+use crate::sound::*;
+use crate::tracer::*;
+
+#[derive(Parser, Debug)]
+#[command(about = "Space Invaders arcade emulator")]
+pub struct Options {
+    /// Game to run: invaders, invadpt2, lrescue or ballbomb
+    #[arg(long, default_value = "invaders")]
+    pub game: String,
+
+    /// ROM set to run: a directory of chip files, a MAME .zip or a single ROM image (followed by the colour PROMs for --overlay prom)
+    #[arg(long, default_value = "rom")]
+    pub rom: PathBuf,
+
+    /// What to do when the game writes to ROM
+    #[arg(long, value_enum, default_value_t = RomWritePolicy::Ignore)]
+    pub rom_writes: RomWritePolicy,
+
+    /// Colour overlay over the black and white monitor
+    #[arg(long, value_enum, default_value_t = OverlayMode::None)]
+    pub overlay: OverlayMode,
+
+    /// Use an overlay from a PPM image or a band definition file instead
+    #[arg(long)]
+    pub overlay_file: Option<PathBuf>,
+
+    /// Where the sound effects come from: sample WAVs or the built-in synthesizer
+    #[arg(long, value_enum, default_value_t = SoundMode::Samples)]
+    pub sound: SoundMode,
+
+    /// Directory holding the sound samples 0.wav - 9.wav
+    #[arg(long, default_value = "samples")]
+    pub samples: PathBuf,
+
+    /// Record the game's audio to a WAV file
+    #[arg(long)]
+    pub record_audio: Option<PathBuf>,
+
+    /// Reset the CPU if the game stops kicking the watchdog for this many frames (255 if not given, as on the hardware)
+    #[arg(long, value_name = "FRAMES", num_args = 0..=1, default_missing_value = "255",
+          value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub watchdog: Option<usize>,
+
+    /// Print a disassembly of the ROM set and exit
+    #[arg(long)]
+    pub disassemble: bool,
+
+    /// Write a reassemblable .asm file of the ROM set, with code and data separated, and exit
+    #[arg(long, value_name = "FILE")]
+    pub asm: Option<PathBuf>,
+
+    /// Run without a window or sound device
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Stop after this many frames
+    #[arg(long)]
+    pub frames: Option<u64>,
+
+    /// Open the debugger console before starting
+    #[arg(long)]
+    pub debug: bool,
+
+    /// Stop in the debugger when the CPU reaches this address (hex, repeatable)
+    #[arg(long = "break", value_name = "ADDR", value_parser = parse_address)]
+    pub breakpoints: Vec<Word>,
+
+    /// Log every instruction executed to a file
+    #[arg(long, value_name = "FILE")]
+    pub trace: Option<PathBuf>,
+
+    /// Only trace instructions in this PC range (hex, e.g. 0100-01FF)
+    #[arg(long, value_name = "START-END", value_parser = parse_address_range)]
+    pub trace_range: Option<(Word, Word)>,
+
+    /// Leave this many instructions out of the start of the trace
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub trace_skip: u64,
+
+    /// Stop tracing after this many instructions
+    #[arg(long, value_name = "COUNT")]
+    pub trace_limit: Option<u64>,
+
+    /// Stop in the debugger when the CPU touches an address: ADDR or ADDR:w for a write, ADDR:r for a read, ADDR=VALUE for a write of VALUE (hex, repeatable)
+    #[arg(long = "watch", value_name = "WATCHPOINT", value_parser = parse_watchpoint)]
+    pub watchpoints: Vec<Watchpoint>,
+
+    /// Read cabinet settings from a config file
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Ships per game (3-6, depending on the game)
+    #[arg(long)]
+    pub ships: Option<u8>,
+
+    /// Score at which an extra ship is awarded (1000 or 1500)
+    #[arg(long)]
+    pub extra_ship_at: Option<u16>,
+
+    /// Show coin info in the attract mode (on or off)
+    #[arg(long, value_parser = parse_switch)]
+    pub coin_info: Option<bool>,
+}
+
+impl Options {
+
+    pub fn trace_filter(&self) -> TraceFilter {
+        TraceFilter {
+            pc_range: self.trace_range,
+            skip: self.trace_skip,
+            limit: self.trace_limit,
+        }
+    }
+
+    pub fn dip_switches(&self) -> Result<DipSwitches, String> {
+        // Start from the config file if there is one, then let anything
+        // given on the command line override it
+        let mut dip_switches = match &self.config {
+            Some(path) => read_config_file(path)?,
+            None => DipSwitches::new(),
+        };
+
+        if let Some(ships) = self.ships {
+            dip_switches.set_ships(ships)?;
+        }
+
+        if let Some(score) = self.extra_ship_at {
+            dip_switches.set_extra_ship_at(score)?;
+        }
+
+        if let Some(coin_info) = self.coin_info {
+            dip_switches.coin_info = coin_info;
+        }
+
+        Ok(dip_switches)
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<DipSwitches, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read config file {}: {}", path.display(), e))?;
+
+    parse_config(&contents)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse_config(contents: &str) -> Result<DipSwitches, String> {
+    // The config file is a list of "key = value" settings, one per line.
+    // Blank lines and anything after a # are ignored:
+    //
+    //   ships = 5
+    //   extra_ship_at = 1000
+    //   coin_info = off
+    let mut dip_switches = DipSwitches::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = line.split_once('=')
+            .ok_or(format!("line {}: expected key = value", number + 1))?;
+        let (key, value) = (key.trim(), value.trim());
+
+        let result = match key {
+            "ships" => parse_number(value).and_then(|ships| dip_switches.set_ships(ships)),
+            "extra_ship_at" => parse_number(value).and_then(|score| dip_switches.set_extra_ship_at(score)),
+            "coin_info" => parse_switch(value).map(|on| dip_switches.coin_info = on),
+            _ => Err(format!("unknown setting '{}'", key)),
+        };
+
+        result.map_err(|e| format!("line {}: {}", number + 1, e))?;
+    }
+
+    Ok(dip_switches)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("'{}' is not a valid number", value))
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => Err(format!("'{}' is not on or off", value)),
+    }
+}
+
+fn parse_address_range(value: &str) -> Result<(Word, Word), String> {
+    let (start, end) = value.split_once('-')
+        .ok_or(format!("'{}' is not a range, expected START-END", value))?;
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+
+    if start > end {
+        return Err(format!("'{}' ends before it starts", value));
+    }
+
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let dip_switches = parse_config("
+            # Operator settings
+            ships = 5
+            extra_ship_at = 1000  # easier
+            coin_info = off
+        ").unwrap();
+
+        assert_eq!(dip_switches.ships, 5);
+        assert_eq!(dip_switches.extra_ship_at, Some(1000));
+        assert!(!dip_switches.coin_info);
+
+        assert!(parse_config("ships = 9").is_err());
+        assert!(parse_config("lives = 3").is_err());
+        assert!(parse_config("coin_info").is_err());
+    }
+
+    #[test]
+    fn test_watchdog_option() {
+        assert_eq!(Options::try_parse_from(["invaders", "--watchdog"]).unwrap().watchdog, Some(255));
+        assert_eq!(Options::try_parse_from(["invaders", "--watchdog", "10"]).unwrap().watchdog, Some(10));
+        assert!(Options::try_parse_from(["invaders", "--watchdog", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_address_range() {
+        assert_eq!(parse_address_range("0100-01ff"), Ok((0x0100, 0x01FF)));
+        assert!(parse_address_range("0100").is_err());
+        assert!(parse_address_range("0200-0100").is_err());
+    }
+}
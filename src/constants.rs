@@ -12,9 +12,31 @@ pub const DISPLAY_FACTOR: u32 = 2;
 pub const CLOCK_SPEED: usize = 2_000_000;  // 2 MHz
 pub const MAX_CYCLES_PER_FRAME: usize = CLOCK_SPEED / 60;
 
+// The video hardware interrupts the CPU twice a frame. RST 1 fires when the beam
+// reaches the middle of the screen (line 96) and RST 2 when it reaches the end of
+// the visible picture (line 224). The game uses them to redraw whichever half of
+// the screen the beam is not currently drawing.
+pub const SCANLINES_PER_FRAME: usize = 262;
+pub const CYCLES_PER_SCANLINE: usize = MAX_CYCLES_PER_FRAME / SCANLINES_PER_FRAME;
+pub const MID_SCREEN_CYCLES: usize = 96 * CYCLES_PER_SCANLINE;
+pub const VBLANK_CYCLES: usize = 224 * CYCLES_PER_SCANLINE;
+pub const MID_SCREEN_INTERRUPT: Byte = 1;
+pub const VBLANK_INTERRUPT: Byte = 2;
+
+// Audio is mixed as 16-bit mono at 44.1 kHz, one frame's worth at a time
+pub const SAMPLE_RATE: usize = 44100;
+pub const SAMPLES_PER_FRAME: usize = SAMPLE_RATE / 60;
+
+// While halted the CPU idles in 4 cycle steps waiting for an interrupt
+pub const HALT_IDLE_CYCLES: u8 = 4;
+
 // We can address from 0 - 65535 in memory (i.e. 0x0000 - 0xFFFF)
 pub const MEMORY_SIZE: usize = 65536;
 
+// The screen is drawn from video RAM at 0x2400 - 0x3FFF
+pub const VRAM_START: Word = 0x2400;
+pub const VRAM_SIZE: usize = 0x1C00;
+
 // Flags
 // The following bits are used with register A as a flags Register. The following bits
 // represent the following flags:
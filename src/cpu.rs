@@ -1,6 +1,8 @@
 use crate::bus::*;
 use crate::constants::*;
+use crate::io::*;
 use crate::ops::*;
+use crate::tracer::*;
 use crate::utils::*;
 
 #[derive(Debug, Copy, Clone)]
@@ -14,6 +16,32 @@ union RegisterPair {
     parts: RegisterPairParts,
 }
 
+// The registers as the debugger sees them, either alone or in pairs
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+// What a call to execute did: the cycles it took, and the RST vector of the
+// interrupt it serviced in place of the next instruction, if any
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Execution {
+    pub cycles: u8,
+    pub interrupt: Option<Byte>,
+}
+
 // This is the Intel 8080 CPU
 pub struct Cpu {
     // There are 7 general purpose registers - B C D E H L and Accumulator (A).
@@ -32,6 +60,20 @@ pub struct Cpu {
     program_counter: Word,
     stack_pointer: Word,
 
+    // The interrupt enable flip-flop (INTE). EI only takes effect once the
+    // instruction following it has executed, so we track that delay separately.
+    // An interrupt request is latched until the CPU is able to service it
+    interrupts_enabled: bool,
+    interrupt_enable_delay: bool,
+    interrupt_request: Option<Byte>,
+
+    // Set by HLT, the CPU does nothing until an interrupt is accepted
+    halted: bool,
+
+    // Cycles executed since power on, and the tracer logging each instruction
+    total_cycles: u64,
+    tracer: Option<Tracer>,
+
     bus: Bus,
 }
 
@@ -45,18 +87,148 @@ impl Cpu {
             hl: RegisterPair { val: 0 },
             program_counter: 0,
             stack_pointer: 0,
+            interrupts_enabled: false,
+            interrupt_enable_delay: false,
+            interrupt_request: None,
+            halted: false,
+            total_cycles: 0,
+            tracer: None,
             bus: bus
         }
     }
 
     pub fn reset(&mut self) {
+        // A reset restarts the program from address 0. The hardware leaves the
+        // other registers as they were, which the game doesn't rely on
         self.af.val = 0x0001;  // Sets an unsed part of flags that should always be 1
+        self.program_counter = 0;
+        self.interrupts_enabled = false;
+        self.interrupt_enable_delay = false;
+        self.interrupt_request = None;
+        self.halted = false;
+    }
+
+    pub fn program_counter(&self) -> Word {
+        self.program_counter
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    pub fn get_register(&self, register: Register) -> Word {
+        unsafe {
+            match register {
+                Register::A => self.af.parts.hi as Word,
+                Register::F => self.af.parts.lo as Word,
+                Register::B => self.bc.parts.hi as Word,
+                Register::C => self.bc.parts.lo as Word,
+                Register::D => self.de.parts.hi as Word,
+                Register::E => self.de.parts.lo as Word,
+                Register::H => self.hl.parts.hi as Word,
+                Register::L => self.hl.parts.lo as Word,
+                Register::BC => self.bc.val,
+                Register::DE => self.de.val,
+                Register::HL => self.hl.val,
+                Register::SP => self.stack_pointer,
+                Register::PC => self.program_counter,
+            }
+        }
+    }
+
+    // Single registers only take the low byte of value
+    pub fn set_register(&mut self, register: Register, value: Word) {
+        let byte = value as Byte;
+
+        match register {
+            Register::A => self.af.parts.hi = byte,
+            Register::F => self.af.parts.lo = byte,
+            Register::B => self.bc.parts.hi = byte,
+            Register::C => self.bc.parts.lo = byte,
+            Register::D => self.de.parts.hi = byte,
+            Register::E => self.de.parts.lo = byte,
+            Register::H => self.hl.parts.hi = byte,
+            Register::L => self.hl.parts.lo = byte,
+            Register::BC => self.bc.val = value,
+            Register::DE => self.de.val = value,
+            Register::HL => self.hl.val = value,
+            Register::SP => self.stack_pointer = value,
+            Register::PC => self.program_counter = value,
+        };
+    }
+
+    // Describes the CPU state for the debugger, with each flag shown by its
+    // letter when set:
+    //   A:12 F:57 [-Z-A-P-C] BC:0000 DE:0000 HL:2400 SP:2400 PC:0A3F INTE HLT
+    pub fn debug(&self) -> String {
+        let flag = |position: usize, name: char| if self.get_register(Register::F) & (1 << position) != 0 { name } else { '-' };
+        let flags: String = [
+            flag(SIGN_FLAG, 'S'), flag(ZERO_FLAG, 'Z'), '-', flag(AUXILIARY_CARRY_FLAG, 'A'),
+            '-', flag(PARITY_FLAG, 'P'), '-', flag(CARRY_FLAG, 'C'),
+        ].iter().collect();
+
+        let mut state = format!(
+            "A:{:02X} F:{:02X} [{}] BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X} PC:{:04X}",
+            self.get_register(Register::A), self.get_register(Register::F), flags,
+            self.get_register(Register::BC), self.get_register(Register::DE), self.get_register(Register::HL),
+            self.stack_pointer, self.program_counter,
+        );
+
+        if self.interrupts_enabled {
+            state.push_str(" INTE");
+        }
+
+        if self.halted {
+            state.push_str(" HLT");
+        }
+
+        state
+    }
+
+    pub fn request_interrupt(&mut self, rst_vector: Byte) {
+        // External hardware places an RST instruction on the data bus when
+        // it interrupts the CPU. Latch the vector until it can be serviced
+        if rst_vector > 7 {
+            panic!("Invalid RST vector {} requested for interrupt", rst_vector);
+        }
+
+        self.interrupt_request = Some(rst_vector);
+    }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
     }
 
-    pub fn debug(&self) {
+    pub fn execute(&mut self, io: &mut dyn IoPorts) -> Execution {
+        // Interrupts are only serviced between instructions
+        let execution = match self.handle_interrupts() {
+            Some((rst_vector, cycles)) => Execution { cycles: cycles, interrupt: Some(rst_vector) },
+            None if self.halted => Execution { cycles: HALT_IDLE_CYCLES, interrupt: None },
+            None => Execution { cycles: self.execute_next(io), interrupt: None },
+        };
+
+        self.total_cycles += execution.cycles as u64;
+        execution
     }
 
-    pub fn execute(&mut self) -> u8 {
+    fn execute_next(&mut self, io: &mut dyn IoPorts) -> u8 {
+        if let Some(mut tracer) = self.tracer.take() {
+            tracer.trace(self, self.total_cycles);
+            self.tracer = Some(tracer);
+        }
+
         // Execute operation and return cycles needed execute
         let op = self.read_memory(self.program_counter);
         let opcode = OPCODE_MAP
@@ -69,6 +241,8 @@ impl Cpu {
             Operation::ADD | Operation::ADI => self.do_add(opcode, false),
             Operation::ADC | Operation::ACI => self.do_add(opcode, true),
             Operation::ANA | Operation::ANI => self.do_and(opcode),
+            Operation::CALL | Operation::CNZ | Operation::CZ | Operation::CNC | Operation::CC |
+            Operation::CPO | Operation::CPE | Operation::CP | Operation::CM => self.do_call(opcode),
             Operation::CMA => self.do_complement_accumulator(opcode),
             Operation::CMC => self.do_complement_carry(opcode),
             Operation::CMP | Operation::CPI => self.do_compare(opcode),
@@ -76,20 +250,30 @@ impl Cpu {
             Operation::DAD => self.do_double_add(opcode),
             Operation::DCR => self.do_decrement(opcode),
             Operation::DCX => self.do_decrement_pair(opcode),
+            Operation::DI => self.do_disable_interrupts(opcode),
+            Operation::EI => self.do_enable_interrupts(opcode),
+            Operation::HLT => self.do_halt(opcode),
+            Operation::IN => self.do_input(opcode, io),
             Operation::INR => self.do_increment(opcode),
             Operation::INX => self.do_increment_pair(opcode),
+            Operation::JMP | Operation::JNZ | Operation::JZ | Operation::JNC | Operation::JC |
+            Operation::JPO | Operation::JPE | Operation::JP | Operation::JM => self.do_jump(opcode),
             Operation::LDAX => self.do_load_accumulator(opcode),
             Operation::LHLD => self.do_load_hl_direct(opcode),
             Operation::MOV | Operation::MVI => self.do_move(opcode),
             Operation::NOP => opcode.cycles,
             Operation::ORA | Operation::ORI => self.do_or(opcode),
+            Operation::OUT => self.do_output(opcode, io),
             Operation::PCHL => self.do_load_program_counter(opcode),
             Operation::POP => self.do_pop(opcode),
             Operation::PUSH => self.do_push(opcode),
             Operation::RAL => self.do_rotate_left(opcode, true),
             Operation::RAR => self.do_rotate_right(opcode, true),
+            Operation::RET | Operation::RNZ | Operation::RZ | Operation::RNC | Operation::RC |
+            Operation::RPO | Operation::RPE | Operation::RP | Operation::RM => self.do_return(opcode),
             Operation::RLC => self.do_rotate_left(opcode, false),
             Operation::RRC => self.do_rotate_right(opcode, false),
+            Operation::RST => self.do_restart(opcode),
             Operation::SBB | Operation::SBI => self.do_sub(opcode, true),
             Operation::SHLD => self.do_store_hl_direct(opcode),
             Operation::SPHL => self.do_load_stack_pointer(opcode),
@@ -103,6 +287,31 @@ impl Cpu {
         }
     }
 
+    // Services a pending interrupt, returning its RST vector and cycles
+    fn handle_interrupts(&mut self) -> Option<(Byte, u8)> {
+        // The instruction directly after EI always executes before an
+        // interrupt can be accepted
+        if self.interrupt_enable_delay {
+            self.interrupt_enable_delay = false;
+            return None;
+        }
+
+        if !self.interrupts_enabled {
+            return None;
+        }
+
+        let rst_vector = self.interrupt_request.take()?;
+
+        // Accepting an interrupt resets INTE and wakes a halted CPU, then
+        // the RST supplied by the hardware is executed like any other instruction
+        self.interrupts_enabled = false;
+        self.halted = false;
+
+        let code = 0xC7 | (rst_vector << 3);
+        let opcode = OPCODE_MAP.get(&code).unwrap();
+        Some((rst_vector, self.do_restart(opcode)))
+    }
+
     fn read_memory(&self, addr: Word) -> Byte {
         self.bus.read_byte(addr)
     }
@@ -177,6 +386,23 @@ impl Cpu {
         }
     }
 
+    fn is_condition_met(&self, code: Byte) -> bool {
+        // Conditional jumps, calls and returns encode the condition
+        // to test in bits 3-5 of the opcode:
+        //   000 NZ  001 Z  010 NC  011 C  100 PO  101 PE  110 P  111 M
+        match (code >> 3) & 0x07 {
+            0 => !self.is_zero_flag_set(),
+            1 => self.is_zero_flag_set(),
+            2 => !self.is_carry_flag_set(),
+            3 => self.is_carry_flag_set(),
+            4 => !self.is_parity_flag_set(),
+            5 => self.is_parity_flag_set(),
+            6 => !self.is_sign_flag_set(),
+            7 => self.is_sign_flag_set(),
+            _ => unreachable!(),
+        }
+    }
+
     fn update_zero_flag(&mut self, val: bool) {
         unsafe {
             match val {
@@ -291,6 +517,27 @@ impl Cpu {
         }
     }
 
+    fn do_call(&mut self, opcode: &OpCode) -> u8 {
+        // The address is always read, even when the call is not taken
+        let addr = self.get_next_word();
+
+        let should_call = match opcode.code {
+            0xCD => true,
+            0xC4 | 0xCC | 0xD4 | 0xDC | 0xE4 | 0xEC | 0xF4 | 0xFC => self.is_condition_met(opcode.code),
+            _ => panic!("Unexpected code [{:02X}] encountered for CALL or conditional call", opcode.code),
+        };
+
+        if !should_call {
+            return opcode.cycles;
+        }
+
+        self.push_word_to_stack(self.program_counter);
+        self.program_counter = addr;
+
+        // Conditional calls take longer when the call is actually made
+        opcode.alt_cycles.unwrap_or(opcode.cycles)
+    }
+
     fn do_compare(&mut self, opcode: &OpCode) -> u8 {
         unsafe {
             let to_cp = match opcode.code {
@@ -417,6 +664,18 @@ impl Cpu {
         opcode.cycles
     }
 
+    fn do_disable_interrupts(&mut self, opcode: &OpCode) -> u8 {
+        self.interrupts_enabled = false;
+        self.interrupt_enable_delay = false;
+        opcode.cycles
+    }
+
+    fn do_enable_interrupts(&mut self, opcode: &OpCode) -> u8 {
+        self.interrupts_enabled = true;
+        self.interrupt_enable_delay = true;
+        opcode.cycles
+    }
+
     fn do_exchange(&mut self, opcode: &OpCode) -> u8 {
         unsafe { std::mem::swap(&mut self.de.val, &mut self.hl.val); }
         opcode.cycles
@@ -448,6 +707,18 @@ impl Cpu {
         opcode.cycles
     }
 
+    fn do_halt(&mut self, opcode: &OpCode) -> u8 {
+        // PC already points past HLT, so that is where an interrupt will return to
+        self.halted = true;
+        opcode.cycles
+    }
+
+    fn do_input(&mut self, opcode: &OpCode, io: &mut dyn IoPorts) -> u8 {
+        let port = self.get_next_byte();
+        self.af.parts.hi = io.read_port(port);
+        opcode.cycles
+    }
+
     fn do_increment(&mut self, opcode: &OpCode) -> u8 {
         unsafe {
             let res = match opcode.code {
@@ -489,6 +760,23 @@ impl Cpu {
         opcode.cycles
     }
 
+    fn do_jump(&mut self, opcode: &OpCode) -> u8 {
+        // The address is always read, even when the jump is not taken
+        let addr = self.get_next_word();
+
+        let should_jump = match opcode.code {
+            0xC3 => true,
+            0xC2 | 0xCA | 0xD2 | 0xDA | 0xE2 | 0xEA | 0xF2 | 0xFA => self.is_condition_met(opcode.code),
+            _ => panic!("Unexpected code [{:02X}] encountered for JMP or conditional jump", opcode.code),
+        };
+
+        if should_jump {
+            self.program_counter = addr;
+        }
+
+        opcode.cycles
+    }
+
     fn do_load_hl_direct(&mut self, opcode: &OpCode) -> u8 {
         let addr = self.get_next_word();
         self.hl.parts.lo = self.read_memory(addr);
@@ -618,6 +906,12 @@ impl Cpu {
         }
     }
 
+    fn do_output(&mut self, opcode: &OpCode, io: &mut dyn IoPorts) -> u8 {
+        let port = self.get_next_byte();
+        unsafe { io.write_port(port, self.af.parts.hi); }
+        opcode.cycles
+    }
+
     fn do_pop(&mut self, opcode: &OpCode) -> u8 {
         unsafe {
             match opcode.code {
@@ -646,6 +940,34 @@ impl Cpu {
         opcode.cycles
     }
 
+    fn do_restart(&mut self, opcode: &OpCode) -> u8 {
+        // RST n is a one byte CALL to address n * 8, which is encoded
+        // in bits 3-5 of the opcode
+        let addr = (opcode.code & 0x38) as Word;
+
+        self.push_word_to_stack(self.program_counter);
+        self.program_counter = addr;
+
+        opcode.cycles
+    }
+
+    fn do_return(&mut self, opcode: &OpCode) -> u8 {
+        let should_return = match opcode.code {
+            0xC9 => true,
+            0xC0 | 0xC8 | 0xD0 | 0xD8 | 0xE0 | 0xE8 | 0xF0 | 0xF8 => self.is_condition_met(opcode.code),
+            _ => panic!("Unexpected code [{:02X}] encountered for RET or conditional return", opcode.code),
+        };
+
+        if !should_return {
+            return opcode.cycles;
+        }
+
+        self.program_counter = self.pop_word_from_stack();
+
+        // Conditional returns take longer when the return is actually made
+        opcode.alt_cycles.unwrap_or(opcode.cycles)
+    }
+
     fn do_rotate_left(&mut self, opcode: &OpCode, through_carry: bool) -> u8 {
         unsafe {
             let most_significant_bit = get_bit_val(&self.af.parts.hi, 7);
@@ -781,6 +1103,30 @@ impl Cpu {
 mod tests {
     use super::*;
 
+    // Records every port access so tests can check what the CPU did
+    struct MockPorts {
+        input: Byte,
+        reads: Vec<Byte>,
+        writes: Vec<(Byte, Byte)>,
+    }
+
+    impl MockPorts {
+        fn new() -> MockPorts {
+            MockPorts { input: 0, reads: Vec::new(), writes: Vec::new() }
+        }
+    }
+
+    impl IoPorts for MockPorts {
+        fn read_port(&mut self, port: Byte) -> Byte {
+            self.reads.push(port);
+            self.input
+        }
+
+        fn write_port(&mut self, port: Byte, data: Byte) {
+            self.writes.push((port, data));
+        }
+    }
+
     #[test]
     fn test_do_add() {
         let mut cpu = Cpu::new(Bus::new());
@@ -848,6 +1194,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_do_call() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        cpu.stack_pointer = 0x2400;
+        cpu.program_counter = 0x1001;
+        cpu.write_memory(0x1001, 0x34);
+        cpu.write_memory(0x1002, 0x12);
+
+        let opcode = OPCODE_MAP.get(&0xCD).unwrap();
+        let cycles = cpu.do_call(opcode);
+
+        assert_eq!(cycles, 17);
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0x23FE);
+        assert_eq!(cpu.pop_word_from_stack(), 0x1003);
+
+        // CNZ with zero set should fall through
+        let opcode = OPCODE_MAP.get(&0xC4).unwrap();
+        cpu.program_counter = 0x1001;
+        cpu.update_zero_flag(true);
+        let cycles = cpu.do_call(opcode);
+
+        assert_eq!(cycles, 11);
+        assert_eq!(cpu.program_counter, 0x1003);
+        assert_eq!(cpu.stack_pointer, 0x2400);
+
+        // CZ with zero set should call and take the longer timing
+        let opcode = OPCODE_MAP.get(&0xCC).unwrap();
+        cpu.program_counter = 0x1001;
+        let cycles = cpu.do_call(opcode);
+
+        assert_eq!(cycles, 17);
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.pop_word_from_stack(), 0x1003);
+    }
+
     #[test]
     fn test_do_compare() {
         let mut cpu = Cpu::new(Bus::new());
@@ -1027,6 +1410,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_do_halt() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        let mut ports = MockPorts::new();
+
+        // EI, HLT at 0x1000
+        cpu.write_memory(0x1000, 0xFB);
+        cpu.write_memory(0x1001, 0x76);
+
+        cpu.stack_pointer = 0x2400;
+        cpu.program_counter = 0x1000;
+
+        assert_eq!(cpu.execute(&mut ports).cycles, 4);
+        assert_eq!(cpu.execute(&mut ports).cycles, 7);
+        assert!(cpu.is_halted());
+
+        // Idle until an interrupt arrives
+        assert_eq!(cpu.execute(&mut ports).cycles, HALT_IDLE_CYCLES);
+        assert_eq!(cpu.execute(&mut ports).cycles, HALT_IDLE_CYCLES);
+        assert_eq!(cpu.program_counter, 0x1002);
+
+        cpu.request_interrupt(2);
+        assert_eq!(cpu.execute(&mut ports), Execution { cycles: 11, interrupt: Some(2) });
+        assert!(!cpu.is_halted());
+        assert_eq!(cpu.program_counter, 0x0010);
+        assert_eq!(cpu.pop_word_from_stack(), 0x1002);
+    }
+
     #[test]
     fn test_do_increment() {
         let mut cpu = Cpu::new(Bus::new());
@@ -1086,6 +1498,66 @@ mod tests {
         unsafe { assert_eq!(cpu.bc.val, 0x1235); }
     }
 
+    #[test]
+    fn test_do_input() {
+        let mut cpu = Cpu::new(Bus::new());
+        let mut ports = MockPorts::new();
+
+        // IN 1
+        cpu.write_memory(0x1000, 0xDB);
+        cpu.write_memory(0x1001, 0x01);
+        cpu.program_counter = 0x1000;
+        ports.input = 0x8F;
+
+        assert_eq!(cpu.execute(&mut ports).cycles, 10);
+        assert_eq!(cpu.program_counter, 0x1002);
+        assert_eq!(ports.reads, vec![0x01]);
+        assert!(ports.writes.is_empty());
+        unsafe { assert_eq!(cpu.af.parts.hi, 0x8F); }
+    }
+
+    #[test]
+    fn test_do_jump() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        // JMP always jumps
+        cpu.program_counter = 0x1000;
+        cpu.write_memory(0x1000, 0x34);
+        cpu.write_memory(0x1001, 0x12);
+        let opcode = OPCODE_MAP.get(&0xC3).unwrap();
+        cpu.do_jump(opcode);
+        assert_eq!(cpu.program_counter, 0x1234);
+
+        // Each conditional jump is checked with its flag set and reset, along
+        // with whether the jump should be taken when the flag is set
+        let cases: [(Byte, bool); 8] = [
+            (0xC2, false), (0xCA, true),
+            (0xD2, false), (0xDA, true),
+            (0xE2, false), (0xEA, true),
+            (0xF2, false), (0xFA, true),
+        ];
+
+        for (code, jump_when_set) in cases {
+            let opcode = OPCODE_MAP.get(&code).unwrap();
+
+            for flag in [true, false] {
+                match code & 0xF0 {
+                    0xC0 => cpu.update_zero_flag(flag),
+                    0xD0 => cpu.update_carry_flag(flag),
+                    0xE0 => cpu.update_parity_flag(flag),
+                    _ => cpu.update_sign_flag(flag),
+                };
+
+                cpu.program_counter = 0x1000;
+                let cycles = cpu.do_jump(opcode);
+
+                let expected = if flag == jump_when_set { 0x1234 } else { 0x1002 };
+                assert_eq!(cpu.program_counter, expected, "opcode {:02X} with flag {}", code, flag);
+                assert_eq!(cycles, 10);
+            }
+        }
+    }
+
     #[test]
     fn test_do_load_accumulator() {
         let mut cpu = Cpu::new(Bus::new());
@@ -1131,6 +1603,142 @@ mod tests {
         unsafe { assert_eq!(cpu.bc.parts.lo, 0x23); }
     }
 
+    #[test]
+    fn test_do_output() {
+        let mut cpu = Cpu::new(Bus::new());
+        let mut ports = MockPorts::new();
+
+        // OUT 4
+        cpu.write_memory(0x1000, 0xD3);
+        cpu.write_memory(0x1001, 0x04);
+        cpu.program_counter = 0x1000;
+        cpu.af.parts.hi = 0xA5;
+
+        assert_eq!(cpu.execute(&mut ports).cycles, 10);
+        assert_eq!(cpu.program_counter, 0x1002);
+        assert_eq!(ports.writes, vec![(0x04, 0xA5)]);
+        assert!(ports.reads.is_empty());
+    }
+
+    #[test]
+    fn test_do_restart() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        cpu.stack_pointer = 0x2400;
+        cpu.program_counter = 0x1235;
+
+        let opcode = OPCODE_MAP.get(&0xD7).unwrap();
+        let cycles = cpu.do_restart(opcode);
+
+        assert_eq!(cycles, 11);
+        assert_eq!(cpu.program_counter, 0x0010);
+        assert_eq!(cpu.pop_word_from_stack(), 0x1235);
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        cpu.set_register(Register::HL, 0x1234);
+        cpu.set_register(Register::A, 0x1FF);
+        cpu.set_register(Register::C, 0x56);
+
+        assert_eq!(cpu.get_register(Register::H), 0x12);
+        assert_eq!(cpu.get_register(Register::L), 0x34);
+        assert_eq!(cpu.get_register(Register::A), 0xFF);
+        assert_eq!(cpu.get_register(Register::BC), 0x0056);
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut cpu = Cpu::new(Bus::new());
+        cpu.reset();
+        cpu.set_register(Register::A, 0x12);
+        cpu.set_register(Register::F, 0x47);
+        cpu.set_register(Register::SP, 0x2400);
+        cpu.halted = true;
+
+        assert_eq!(cpu.debug(), "A:12 F:47 [-Z---P-C] BC:0000 DE:0000 HL:0000 SP:2400 PC:0000 HLT");
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut cpu = Cpu::new(Bus::new());
+        cpu.program_counter = 0x1234;
+        cpu.interrupts_enabled = true;
+        cpu.halted = true;
+
+        cpu.reset();
+
+        assert_eq!(cpu.program_counter(), 0x0000);
+        assert!(!cpu.interrupts_enabled);
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn test_request_interrupt() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        let mut ports = MockPorts::new();
+
+        // EI, NOP, NOP at 0x1000
+        cpu.write_memory(0x1000, 0xFB);
+        cpu.write_memory(0x1001, 0x00);
+        cpu.write_memory(0x1002, 0x00);
+
+        cpu.stack_pointer = 0x2400;
+        cpu.program_counter = 0x1000;
+
+        // With interrupts disabled the request stays latched
+        cpu.request_interrupt(1);
+        assert_eq!(cpu.execute(&mut ports).cycles, 4);
+        assert_eq!(cpu.program_counter, 0x1001);
+        assert!(cpu.interrupts_enabled);
+
+        // The instruction after EI still executes before the interrupt
+        assert_eq!(cpu.execute(&mut ports), Execution { cycles: 4, interrupt: None });
+        assert_eq!(cpu.program_counter, 0x1002);
+
+        assert_eq!(cpu.execute(&mut ports), Execution { cycles: 11, interrupt: Some(1) });
+        assert_eq!(cpu.program_counter, 0x0008);
+        assert!(!cpu.interrupts_enabled);
+        assert_eq!(cpu.interrupt_request, None);
+        assert_eq!(cpu.pop_word_from_stack(), 0x1002);
+    }
+
+    #[test]
+    fn test_do_return() {
+        let mut cpu = Cpu::new(Bus::new());
+
+        cpu.stack_pointer = 0x2400;
+        cpu.push_word_to_stack(0x1234);
+
+        let opcode = OPCODE_MAP.get(&0xC9).unwrap();
+        let cycles = cpu.do_return(opcode);
+
+        assert_eq!(cycles, 10);
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0x2400);
+
+        // RC with carry reset should fall through
+        cpu.push_word_to_stack(0x5678);
+        let opcode = OPCODE_MAP.get(&0xD8).unwrap();
+        cpu.update_carry_flag(false);
+        let cycles = cpu.do_return(opcode);
+
+        assert_eq!(cycles, 5);
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0x23FE);
+
+        // RNC with carry reset should return and take the longer timing
+        let opcode = OPCODE_MAP.get(&0xD0).unwrap();
+        let cycles = cpu.do_return(opcode);
+
+        assert_eq!(cycles, 11);
+        assert_eq!(cpu.program_counter, 0x5678);
+        assert_eq!(cpu.stack_pointer, 0x2400);
+    }
+
     #[test]
     fn test_do_rotate_left() {
         let mut cpu = Cpu::new(Bus::new());
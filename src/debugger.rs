@@ -0,0 +1,431 @@
+use std::io::{BufRead, Write};
+
+use crate::bus::*;
+use crate::constants::*;
+use crate::cpu::*;
+use crate::disassembler::*;
+use crate::emulator::*;
+use crate::ops::*;
+
+// What the emulator should do once the console is closed
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DebuggerAction {
+    Continue,
+    Quit,
+}
+
+const HELP: &str = "\
+Addresses and values are in hex, counts in decimal
+  s, step [count]         Execute the next instruction(s)
+  n, next                 Step over a CALL or RST, otherwise step
+  c, continue             Resume the game
+  u, until <addr>         Resume the game until the CPU reaches addr
+  r, regs                 Show the registers
+  x, mem <addr> [len]     Hexdump memory
+  b, break [addr]         Set a breakpoint, or list them all
+  d, delete <addr>        Clear a breakpoint
+  w, watch [addr[:r|:w|=value]]
+                          Stop when the CPU writes to addr (the default), reads
+                          from it or writes value to it, or list watchpoints
+  unwatch <addr>          Clear the watchpoints on addr
+  set <reg> <value>       Change a register (A-L, BC, DE, HL, SP or PC)
+  poke <addr> <bytes..>   Change memory, ROM included
+  q, quit                 Exit the emulator
+";
+
+// A command line monitor for poking at the game while it is stopped. It
+// reads commands until told to resume or quit
+pub fn run_console(emulator: &mut Emulator, input: &mut dyn BufRead, output: &mut dyn Write) -> DebuggerAction {
+    let _ = write!(output, "{}", describe_instruction(emulator));
+
+    loop {
+        let _ = write!(output, "> ");
+        let _ = output.flush();
+
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return DebuggerAction::Quit,
+            _ => (),
+        };
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        let mut text = String::new();
+        let result = run_command(emulator, &words, &mut text);
+        let _ = write!(output, "{}", text);
+
+        match result {
+            Ok(Some(action)) => return action,
+            Ok(None) => (),
+            Err(e) => { let _ = writeln!(output, "{}", e); },
+        };
+    }
+}
+
+pub fn parse_address(value: &str) -> Result<Word, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    Word::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex address", value))
+}
+
+// A watchpoint is written as the address followed by what to watch for:
+//   20F8     a write
+//   20F8:w   a write
+//   20F8:r   a read
+//   20F8=05  a write of 05
+pub fn parse_watchpoint(value: &str) -> Result<Watchpoint, String> {
+    let (addr, kind) = if let Some((addr, data)) = value.split_once('=') {
+        let data = parse_address(data)?;
+        if data > 0xFF {
+            return Err(format!("{:X} is not a byte", data));
+        }
+
+        (addr, WatchKind::Value(data as Byte))
+    } else {
+        match value.split_once(':') {
+            Some((addr, "r")) => (addr, WatchKind::Read),
+            Some((addr, "w")) => (addr, WatchKind::Write),
+            Some((_, kind)) => return Err(format!("'{}' is not r or w", kind)),
+            None => (value, WatchKind::Write),
+        }
+    };
+
+    Ok(Watchpoint { addr: parse_address(addr)?, kind: kind })
+}
+
+// Describes why running stopped part way through a frame, if it did
+pub fn describe_stop(result: RunResult) -> Option<String> {
+    match result {
+        RunResult::FrameComplete => None,
+        RunResult::Breakpoint(addr) => Some(format!("Breakpoint at {:04X}", addr)),
+        RunResult::Watchpoint { by, hit } => Some(format!("Watchpoint: {} by {}", hit, by)),
+        RunResult::RomWrite { by, write } => Some(format!("Illegal {} by {}", write, by)),
+    }
+}
+
+fn describe_watchpoint(watchpoint: &Watchpoint) -> String {
+    match watchpoint.kind {
+        WatchKind::Read => format!("{:04X}:r", watchpoint.addr),
+        WatchKind::Write => format!("{:04X}:w", watchpoint.addr),
+        WatchKind::Value(data) => format!("{:04X}={:02X}", watchpoint.addr, data),
+    }
+}
+
+fn run_command(emulator: &mut Emulator, words: &[&str], text: &mut String) -> Result<Option<DebuggerAction>, String> {
+    let arg = |index: usize| words.get(index).copied()
+        .ok_or(format!("{} needs more arguments, see help", words[0]));
+
+    match words[0] {
+        "s" | "step" => {
+            let count = match words.get(1) {
+                Some(count) => count.parse::<usize>().map_err(|_| format!("'{}' is not a count", count))?,
+                None => 1,
+            };
+
+            for _ in 0..count {
+                if let Some(stop) = emulator.step().and_then(describe_stop) {
+                    text.push_str(&format!("{}\n", stop));
+                    break;
+                }
+            }
+            text.push_str(&describe_instruction(emulator));
+        },
+        "n" | "next" => {
+            let pc = emulator.cpu().program_counter();
+            let code = emulator.cpu().bus().peek(pc);
+
+            // Let the subroutine run and stop once it returns
+            if let Some(opcode) = OPCODE_MAP.get(&code).filter(|opcode| is_subroutine_call(opcode.operation)) {
+                emulator.run_to(pc.wrapping_add(opcode.len as Word));
+                return Ok(Some(DebuggerAction::Continue));
+            }
+
+            if let Some(stop) = emulator.step().and_then(describe_stop) {
+                text.push_str(&format!("{}\n", stop));
+            }
+            text.push_str(&describe_instruction(emulator));
+        },
+        "c" | "continue" => return Ok(Some(DebuggerAction::Continue)),
+        "u" | "until" => {
+            emulator.run_to(parse_address(arg(1)?)?);
+            return Ok(Some(DebuggerAction::Continue));
+        },
+        "r" | "regs" => text.push_str(&format!("{}\n", emulator.cpu().debug())),
+        "x" | "mem" => {
+            let addr = parse_address(arg(1)?)?;
+            let len = match words.get(2) {
+                Some(len) => len.parse::<Word>().map_err(|_| format!("'{}' is not a count", len))?,
+                None => 64,
+            };
+
+            text.push_str(&hexdump(emulator, addr, len));
+        },
+        "b" | "break" => match words.get(1) {
+            Some(addr) => emulator.add_breakpoint(parse_address(addr)?),
+            None => {
+                for addr in emulator.breakpoints() {
+                    text.push_str(&format!("{:04X}\n", addr));
+                }
+            },
+        },
+        "d" | "delete" => {
+            let addr = parse_address(arg(1)?)?;
+            if !emulator.remove_breakpoint(addr) {
+                return Err(format!("No breakpoint at {:04X}", addr));
+            }
+        },
+        "w" | "watch" => match words.get(1) {
+            Some(watchpoint) => emulator.cpu_mut().bus_mut().add_watchpoint(parse_watchpoint(watchpoint)?),
+            None => {
+                for watchpoint in emulator.cpu().bus().watchpoints() {
+                    text.push_str(&format!("{}\n", describe_watchpoint(watchpoint)));
+                }
+            },
+        },
+        "unwatch" => {
+            let addr = parse_address(arg(1)?)?;
+            if !emulator.cpu_mut().bus_mut().remove_watchpoints(addr) {
+                return Err(format!("No watchpoint at {:04X}", addr));
+            }
+        },
+        "set" => {
+            let register = parse_register(arg(1)?)?;
+            let value = parse_address(arg(2)?)?;
+            let is_pair = matches!(register, Register::BC | Register::DE | Register::HL | Register::SP | Register::PC);
+
+            if !is_pair && value > 0xFF {
+                return Err(format!("{:X} does not fit in {:?}", value, register));
+            }
+
+            emulator.cpu_mut().set_register(register, value);
+            text.push_str(&format!("{}\n", emulator.cpu().debug()));
+        },
+        "poke" => {
+            let addr = parse_address(arg(1)?)?;
+            arg(2)?;
+
+            for (offset, value) in words[2..].iter().enumerate() {
+                let data = parse_address(value)?;
+                if data > 0xFF {
+                    return Err(format!("{:X} is not a byte", data));
+                }
+
+                emulator.cpu_mut().bus_mut().poke(addr.wrapping_add(offset as Word), data as Byte);
+            }
+        },
+        "q" | "quit" => return Ok(Some(DebuggerAction::Quit)),
+        "h" | "help" | "?" => text.push_str(HELP),
+        command => return Err(format!("Unknown command '{}', try help", command)),
+    };
+
+    Ok(None)
+}
+
+fn is_subroutine_call(operation: Operation) -> bool {
+    matches!(operation,
+        Operation::CALL | Operation::CNZ | Operation::CZ | Operation::CNC | Operation::CC |
+        Operation::CPO | Operation::CPE | Operation::CP | Operation::CM | Operation::RST)
+}
+
+fn parse_register(name: &str) -> Result<Register, String> {
+    let register = match name.to_uppercase().as_str() {
+        "A" => Register::A,
+        "F" => Register::F,
+        "B" => Register::B,
+        "C" => Register::C,
+        "D" => Register::D,
+        "E" => Register::E,
+        "H" => Register::H,
+        "L" => Register::L,
+        "BC" => Register::BC,
+        "DE" => Register::DE,
+        "HL" => Register::HL,
+        "SP" => Register::SP,
+        "PC" => Register::PC,
+        _ => return Err(format!("Unknown register '{}'", name)),
+    };
+
+    Ok(register)
+}
+
+// The instruction the CPU will execute next, e.g.
+//   0A3F  C3 00 08  JMP 0800H
+fn describe_instruction(emulator: &Emulator) -> String {
+    let cpu = emulator.cpu();
+    format!("{}\n", disassemble(cpu.bus(), cpu.program_counter()))
+}
+
+// 16 bytes a line, followed by any printable ASCII:
+//   2000  00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F  ................
+fn hexdump(emulator: &Emulator, addr: Word, len: Word) -> String {
+    let bus = emulator.cpu().bus();
+    let mut text = String::new();
+
+    for line_start in (0..len).step_by(16) {
+        let line_addr = addr.wrapping_add(line_start);
+        let bytes: Vec<Byte> = (0..16.min(len - line_start))
+            .map(|offset| bus.peek(line_addr.wrapping_add(offset)))
+            .collect();
+
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = bytes.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+
+        text.push_str(&format!("{:04X}  {:<47}  {}\n", line_addr, hex.join(" "), ascii));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dip_switches::*;
+    use crate::drivers::invaders::*;
+    use crate::overlay::*;
+    use crate::rom::*;
+    use crate::sound::*;
+
+    fn test_emulator(program: &[Byte]) -> Emulator {
+        // The program runs from 0x0000, with the rest of ROM left as NOPs
+        let chip = RomChip { name: "test", addr: 0x0000, size: program.len(), crc: 0 };
+        let rom_set = RomSet { chips: vec![(chip, program.to_vec())] };
+
+        Emulator::new(&INVADERS, &rom_set, DipSwitches::new(), Sound::silent(INVADERS.sounds), Overlay::none(), RomWritePolicy::Ignore).unwrap()
+    }
+
+    fn run_commands(emulator: &mut Emulator, commands: &str) -> (DebuggerAction, String) {
+        let mut output = Vec::new();
+        let action = run_console(emulator, &mut commands.as_bytes(), &mut output);
+
+        (action, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_step() {
+        // JMP 0x0010
+        let mut emulator = test_emulator(&[0xC3, 0x10, 0x00]);
+
+        let (action, output) = run_commands(&mut emulator, "step\ns 3\nquit\n");
+
+        assert_eq!(action, DebuggerAction::Quit);
+        assert_eq!(emulator.cpu().program_counter(), 0x0013);
+        assert!(output.starts_with("0000  C3 10 00  JMP 0010H\n> 0010  00        NOP\n"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        // CALL 0x0010, then the subroutine returns straight away
+        let mut emulator = test_emulator(&[0xCD, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                                           0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC9]);
+        emulator.cpu_mut().set_register(Register::SP, 0x2400);
+
+        let (action, output) = run_commands(&mut emulator, "b 10\nb 5\nb\nd 5\nd 5\nc\n");
+        assert_eq!(action, DebuggerAction::Continue);
+        assert!(output.contains("> 0005\n0010\n"));
+        assert!(output.contains("No breakpoint at 0005"));
+
+        assert_eq!(emulator.run(), RunResult::Breakpoint(0x0010));
+        assert_eq!(emulator.cpu().get_register(Register::SP), 0x23FE);
+
+        // Resuming carries on from the breakpoint rather than stopping at it
+        // straight away, so the next stop is after the subroutine returns and
+        // the NOPs lead back to it
+        assert_eq!(emulator.run(), RunResult::Breakpoint(0x0010));
+        assert_eq!(emulator.cpu().get_register(Register::SP), 0x2400);
+    }
+
+    #[test]
+    fn test_step_over() {
+        // CALL 0x0010 with a breakpoint after it
+        let mut emulator = test_emulator(&[0xCD, 0x10, 0x00]);
+        emulator.cpu_mut().set_register(Register::SP, 0x2400);
+        emulator.cpu_mut().bus_mut().poke(0x0010, 0xC9);
+
+        let (action, _) = run_commands(&mut emulator, "next\n");
+        assert_eq!(action, DebuggerAction::Continue);
+        assert_eq!(emulator.run(), RunResult::Breakpoint(0x0003));
+        assert_eq!(emulator.cpu().get_register(Register::SP), 0x2400);
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut emulator = test_emulator(&[0x00]);
+
+        let (_, output) = run_commands(&mut emulator, "set hl 2400\nset a 100\npoke 2000 48 49\nx 2000 4\nregs\n");
+
+        assert_eq!(emulator.cpu().get_register(Register::HL), 0x2400);
+        assert!(output.contains("100 does not fit in A"));
+        assert!(output.contains("2000  48 49 00 00"));
+        assert!(output.contains("HI.."));
+        assert!(output.contains("HL:2400"));
+
+        // The length is a count, so in decimal
+        let (_, output) = run_commands(&mut emulator, "x 2000 10\n");
+        assert!(output.contains("2000  48 49 00 00 00 00 00 00 00 00  "));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        // STA 20F8H, twice through the main loop
+        let mut emulator = test_emulator(&[0x32, 0xF8, 0x20, 0xC3, 0x00, 0x00]);
+
+        let (_, output) = run_commands(&mut emulator, "watch 20f8\nw 2000:r\nw 2001=05\nwatch\nunwatch 2000\nc\n");
+        assert!(output.contains("20F8:w\n2000:r\n2001=05\n"));
+        assert_eq!(emulator.cpu().bus().watchpoints().len(), 2);
+
+        let hit = WatchHit { addr: 0x20F8, kind: WatchKind::Write, data: 0x00 };
+        assert_eq!(emulator.run(), RunResult::Watchpoint { by: AccessBy::Instruction(0x0000), hit: hit });
+        assert_eq!(emulator.cpu().program_counter(), 0x0003);
+
+        // Stepping reports the hit as well
+        let (_, output) = run_commands(&mut emulator, "s 3\n");
+        assert!(output.contains("Watchpoint: write of 00 to 20F8 by the instruction at 0000\n0003"));
+    }
+
+    #[test]
+    fn test_watchpoint_then_breakpoint() {
+        // STA 20F8H, NOP, JMP 0
+        let mut emulator = test_emulator(&[0x32, 0xF8, 0x20, 0x00, 0xC3, 0x00, 0x00]);
+        run_commands(&mut emulator, "watch 20f8\nbreak 3\n");
+
+        // Stopping for the write already stops at the breakpoint, so
+        // continuing doesn't stop there a second time
+        assert!(matches!(emulator.run(), RunResult::Watchpoint { .. }));
+        assert_eq!(emulator.cpu().program_counter(), 0x0003);
+        assert!(matches!(emulator.run(), RunResult::Watchpoint { .. }));
+    }
+
+    #[test]
+    fn test_watchpoint_by_interrupt() {
+        // EI, then loop on JMP 1 until the mid-screen interrupt pushes its
+        // return address onto the stack
+        let mut emulator = test_emulator(&[0xFB, 0xC3, 0x01, 0x00]);
+        run_commands(&mut emulator, "set sp 2400\nwatch 23ff\n");
+
+        let result = emulator.run();
+        assert!(matches!(result, RunResult::Watchpoint { by: AccessBy::Interrupt { rst_vector: MID_SCREEN_INTERRUPT, pc: 0x0001 }, .. }));
+        assert_eq!(describe_stop(result).unwrap(), "Watchpoint: write of 00 to 23FF by the RST 1 interrupt at 0001");
+        assert_eq!(emulator.cpu().program_counter(), 0x0008);
+    }
+
+    #[test]
+    fn test_parse_watchpoint() {
+        assert_eq!(parse_watchpoint("20F8"), Ok(Watchpoint { addr: 0x20F8, kind: WatchKind::Write }));
+        assert_eq!(parse_watchpoint("20F8:r"), Ok(Watchpoint { addr: 0x20F8, kind: WatchKind::Read }));
+        assert_eq!(parse_watchpoint("20F8=1F"), Ok(Watchpoint { addr: 0x20F8, kind: WatchKind::Value(0x1F) }));
+        assert!(parse_watchpoint("20F8:x").is_err());
+        assert!(parse_watchpoint("20F8=100").is_err());
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("1a3F"), Ok(0x1A3F));
+        assert_eq!(parse_address("0x2400"), Ok(0x2400));
+        assert!(parse_address("10000").is_err());
+        assert!(parse_address("zz").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::constants::*;
+use crate::utils::*;
+
+// The operator settings on the cabinet's DIP switches. How they are wired to
+// port 2 depends on the game (see DipLayout)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct DipSwitches {
+    pub ships: u8,
+    // Left in its factory position when not set
+    pub extra_ship_at: Option<u16>,
+    pub coin_info: bool,
+}
+
+impl DipSwitches {
+
+    pub fn new() -> DipSwitches {
+        // Factory settings
+        DipSwitches {
+            ships: 3,
+            extra_ship_at: None,
+            coin_info: true,
+        }
+    }
+
+    pub fn set_ships(&mut self, ships: u8) -> Result<(), String> {
+        match ships {
+            3..=6 => { self.ships = ships; Ok(()) },
+            _ => Err(format!("Ships must be between 3 and 6, got {}", ships)),
+        }
+    }
+
+    pub fn set_extra_ship_at(&mut self, score: u16) -> Result<(), String> {
+        match score {
+            1000 | 1500 => { self.extra_ship_at = Some(score); Ok(()) },
+            _ => Err(format!("Extra ship must be at 1000 or 1500, got {}", score)),
+        }
+    }
+
+}
+
+impl Default for DipSwitches {
+    fn default() -> DipSwitches {
+        DipSwitches::new()
+    }
+}
+
+// How a game reads its DIP switches through port 2:
+//   bit 0-1  Ships per game, looked up in ships
+//   extra_ship is the bit selecting the extra ship score, along with the
+//   score when it is reset and when it is set
+//   coin_info is the bit that hides the coin info in the attract mode
+// Games without a switch can only be run with its factory setting
+#[derive(Debug, Copy, Clone)]
+pub struct DipLayout {
+    pub ships: [u8; 4],
+    pub extra_ship: Option<(usize, [u16; 2])>,
+    pub coin_info: Option<usize>,
+}
+
+impl DipLayout {
+
+    pub fn port_bits(&self, dip_switches: &DipSwitches) -> Result<Byte, String> {
+        let mut bits = match self.ships.iter().position(|&ships| ships == dip_switches.ships) {
+            Some(index) => index as Byte,
+            None => return Err(format!("{} ships is not a setting for this game", dip_switches.ships)),
+        };
+
+        match (self.extra_ship, dip_switches.extra_ship_at) {
+            (_, None) => (),
+            (Some((bit, scores)), Some(score)) if score == scores[1] => set_bit(&mut bits, bit),
+            (Some((_, scores)), Some(score)) if score == scores[0] => (),
+            (_, Some(score)) => return Err(format!("Extra ship at {} is not a setting for this game", score)),
+        };
+
+        if !dip_switches.coin_info {
+            match self.coin_info {
+                Some(bit) => set_bit(&mut bits, bit),
+                None => return Err(String::from("Coin info is not a setting for this game")),
+            };
+        }
+
+        Ok(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_bits() {
+        let layout = DipLayout {
+            ships: [3, 4, 5, 6],
+            extra_ship: Some((3, [1500, 1000])),
+            coin_info: Some(7),
+        };
+
+        let mut dip_switches = DipSwitches::new();
+        assert_eq!(layout.port_bits(&dip_switches), Ok(0b0000_0000));
+
+        dip_switches.set_ships(6).unwrap();
+        assert_eq!(layout.port_bits(&dip_switches), Ok(0b0000_0011));
+
+        dip_switches.set_ships(4).unwrap();
+        dip_switches.set_extra_ship_at(1000).unwrap();
+        dip_switches.coin_info = false;
+        assert_eq!(layout.port_bits(&dip_switches), Ok(0b1000_1001));
+
+        dip_switches.set_extra_ship_at(1500).unwrap();
+        assert_eq!(layout.port_bits(&dip_switches), Ok(0b1000_0001));
+    }
+
+    #[test]
+    fn test_unsupported_settings() {
+        let layout = DipLayout {
+            ships: [3, 4, 3, 4],
+            extra_ship: None,
+            coin_info: None,
+        };
+
+        let mut dip_switches = DipSwitches::new();
+        dip_switches.set_ships(4).unwrap();
+        assert_eq!(layout.port_bits(&dip_switches), Ok(0b0000_0001));
+
+        dip_switches.set_ships(5).unwrap();
+        assert!(layout.port_bits(&dip_switches).is_err());
+
+        dip_switches.set_ships(3).unwrap();
+        dip_switches.set_extra_ship_at(1500).unwrap();
+        assert!(layout.port_bits(&dip_switches).is_err());
+
+        dip_switches.extra_ship_at = None;
+        dip_switches.coin_info = false;
+        assert!(layout.port_bits(&dip_switches).is_err());
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let mut dip_switches = DipSwitches::new();
+
+        assert!(dip_switches.set_ships(2).is_err());
+        assert!(dip_switches.set_ships(7).is_err());
+        assert!(dip_switches.set_extra_ship_at(2000).is_err());
+        assert_eq!(dip_switches, DipSwitches::new());
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+use crate::bus::*;
+use crate::constants::*;
+use crate::ops::*;
+use crate::rom::*;
+
+// A decoded instruction. Bytes that are not a known opcode decode as a
+// single data byte
+#[derive(Debug)]
+pub struct Instruction {
+    pub addr: Word,
+    pub bytes: Vec<Byte>,
+    pub opcode: Option<&'static OpCode>,
+
+    // The immediate value or address following the opcode, if it has one
+    pub operand: Option<Word>,
+}
+
+impl Instruction {
+
+    // Where the following instruction starts
+    pub fn next_addr(&self) -> Word {
+        self.addr.wrapping_add(self.bytes.len() as Word)
+    }
+
+    // The instruction in 8080 assembler syntax, e.g. "MVI B,0FFH" or "JMP 0A3FH"
+    pub fn text(&self) -> String {
+        self.text_with_operand(format_hex)
+    }
+
+    // As text, but letting the caller choose how the operand is written
+    pub fn text_with_operand(&self, format_operand: impl Fn(Word, usize) -> String) -> String {
+        let opcode = match self.opcode {
+            Some(opcode) => opcode,
+            None => return format!("DB {}", format_hex(self.bytes[0] as Word, 2)),
+        };
+
+        match self.operand {
+            Some(operand) => {
+                // Operands follow any register with a comma, or the mnemonic with a space
+                let separator = if opcode.mnemonic.contains(' ') { "," } else { " " };
+                let digits = (opcode.len as usize - 1) * 2;
+
+                format!("{}{}{}", opcode.mnemonic, separator, format_operand(operand, digits))
+            },
+            None => opcode.mnemonic.clone(),
+        }
+    }
+}
+
+// Listing format, e.g. "0A3F  C3 00 08  JMP 0800H"
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        write!(f, "{:04X}  {:<8}  {}", self.addr, bytes.join(" "), self.text())
+    }
+}
+
+pub fn disassemble(bus: &Bus, addr: Word) -> Instruction {
+    let code = bus.peek(addr);
+    let opcode = OPCODE_MAP.get(&code).copied();
+    let len = opcode.map_or(1, |opcode| opcode.len);
+
+    let bytes: Vec<Byte> = (0..len)
+        .map(|offset| bus.peek(addr.wrapping_add(offset as Word)))
+        .collect();
+
+    // Operands are stored little endian
+    let operand = match bytes.len() {
+        2 => Some(bytes[1] as Word),
+        3 => Some(((bytes[2] as Word) << 8) | bytes[1] as Word),
+        _ => None,
+    };
+
+    Instruction {
+        addr: addr,
+        bytes: bytes,
+        opcode: opcode,
+        operand: operand,
+    }
+}
+
+// Disassembles every instruction from start up to and including end
+pub fn disassemble_range(bus: &Bus, start: Word, end: Word) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut addr = start as usize;
+
+    while addr <= end as usize {
+        let instruction = disassemble(bus, addr as Word);
+        addr += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+
+    instructions
+}
+
+// A listing of every chip in a ROM set, decoded linearly from its first byte
+pub fn listing(bus: &Bus, rom_set: &RomSet) -> String {
+    let mut text = String::new();
+
+    for (chip, _) in &rom_set.chips {
+        let end = chip.addr + (chip.size as Word - 1);
+        text.push_str(&format!("; {} {:04X}-{:04X}\n", chip.name, chip.addr, end));
+
+        for instruction in disassemble_range(bus, chip.addr, end) {
+            text.push_str(&format!("{}\n", instruction));
+        }
+    }
+
+    text
+}
+
+// Hex in assembler syntax, which needs a leading 0 when the first digit is a letter
+pub fn format_hex(value: Word, digits: usize) -> String {
+    let hex = format!("{:0width$X}", value, width = digits);
+
+    match hex.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        true => format!("0{}H", hex),
+        false => format!("{}H", hex),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_bus(program: &[Byte]) -> Bus {
+        let mut bus = Bus::new();
+        for (offset, byte) in program.iter().enumerate() {
+            bus.write_byte(0x1000 + offset as Word, *byte);
+        }
+        bus
+    }
+
+    #[test]
+    fn test_disassemble() {
+        // MVI B,0FFH / JMP 0A3FH / ADI 12H / MOV A,M / LDAX D / undocumented
+        let bus = test_bus(&[0x06, 0xFF, 0xC3, 0x3F, 0x0A, 0xC6, 0x12, 0x7E, 0x1A, 0x08]);
+        let instructions = disassemble_range(&bus, 0x1000, 0x1009);
+
+        let text: Vec<String> = instructions.iter().map(|instruction| instruction.text()).collect();
+        assert_eq!(text, ["MVI B,0FFH", "JMP 0A3FH", "ADI 12H", "MOV A,M", "LDAX D", "DB 08H"]);
+
+        assert_eq!(instructions[1].addr, 0x1002);
+        assert_eq!(instructions[1].operand, Some(0x0A3F));
+        assert_eq!(instructions[1].to_string(), "1002  C3 3F 0A  JMP 0A3FH");
+        assert_eq!(instructions[3].to_string(), "1007  7E        MOV A,M");
+    }
+
+    #[test]
+    fn test_listing() {
+        let bus = test_bus(&[0x3E, 0x01, 0xC9]);
+        let chip = RomChip { name: "test", addr: 0x1000, size: 4, crc: 0 };
+        let rom_set = RomSet { chips: vec![(chip, vec![0x3E, 0x01, 0xC9, 0x00])] };
+
+        assert_eq!(listing(&bus, &rom_set), "\
+; test 1000-1003
+1000  3E 01     MVI A,01H
+1002  C9        RET
+1003  00        NOP
+");
+    }
+
+    #[test]
+    fn test_format_hex() {
+        assert_eq!(format_hex(0x12, 2), "12H");
+        assert_eq!(format_hex(0xA, 2), "0AH");
+        assert_eq!(format_hex(0xFF, 2), "0FFH");
+        assert_eq!(format_hex(0x2400, 4), "2400H");
+        assert_eq!(format_hex(0xC000, 4), "0C000H");
+    }
+}
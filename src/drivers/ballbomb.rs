@@ -0,0 +1,57 @@
+use crate::dip_switches::*;
+use crate::rom::*;
+use crate::sound::*;
+
+use super::*;
+
+// Balloon Bomber has one extra program ROM at 0x4000 and two colour PROMs
+pub static BALLBOMB: GameDriver = GameDriver {
+    name: "ballbomb",
+    description: "Balloon Bomber",
+    roms: &BALLBOMB_ROMS,
+    color_proms: &BALLBOMB_COLOR_PROMS,
+    memory_map: &BOARD_MEMORY_MAP,
+    ports: &COLOR_BOARD_PORTS,
+    // The same controls as Space Invaders
+    inputs: &invaders::INVADERS_INPUTS,
+    sounds: &BALLBOMB_SOUNDS,
+    dip_layout: DipLayout {
+        // Balloon Bomber always shows its coin info
+        ships: [3, 4, 5, 6],
+        extra_ship: None,
+        coin_info: None,
+    },
+};
+
+pub const BALLBOMB_ROMS: [RomChip; 5] = [
+    RomChip { name: "tn01", addr: 0x0000, size: 0x0800, crc: 0x551585B5 },
+    RomChip { name: "tn02", addr: 0x0800, size: 0x0800, crc: 0x7E1F734F },
+    RomChip { name: "tn03", addr: 0x1000, size: 0x0800, crc: 0xD93E20BC },
+    RomChip { name: "tn04", addr: 0x1800, size: 0x0800, crc: 0xD0689A22 },
+    RomChip { name: "tn05-1", addr: 0x4000, size: 0x0800, crc: 0x5D74A9A8 },
+];
+
+pub const BALLBOMB_COLOR_PROMS: [RomChip; 2] = [
+    RomChip { name: "tn06", addr: 0x0000, size: 0x0400, crc: 0x7EC554C4 },
+    RomChip { name: "tn07", addr: 0x0400, size: 0x0400, crc: 0xDEB0AC82 },
+];
+
+// The balloons reuse the Space Invaders samples for different events, and
+// nothing loops:
+//
+// Sound latch 1 (OUT 3)
+//   bit 0    Balloon hit (invader death)
+//   bit 1    Shot
+//   bit 2    Base destroyed (player death)
+//   bit 3    Bomb hit (UFO hit)
+//   bit 4    Extra base
+//
+// Sound latch 2 (OUT 5)
+//   bit 0-3  Music, one note per bit
+pub const BALLBOMB_SOUNDS: SoundLayout = SoundLayout {
+    bits: [
+        [Some(INVADER_DIE_SOUND), Some(SHOT_SOUND), Some(PLAYER_DIE_SOUND), Some(UFO_HIT_SOUND), Some(EXTRA_SHIP_SOUND), None, None, None],
+        [Some(FLEET_SOUNDS[0]), Some(FLEET_SOUNDS[1]), Some(FLEET_SOUNDS[2]), Some(FLEET_SOUNDS[3]), None, None, None, None],
+    ],
+    looping: None,
+};
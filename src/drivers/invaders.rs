@@ -0,0 +1,116 @@
+use crate::dip_switches::*;
+use crate::input::*;
+use crate::input::CabinetButton::*;
+use crate::rom::*;
+use crate::sound::*;
+
+use super::*;
+
+pub static INVADERS: GameDriver = GameDriver {
+    name: "invaders",
+    description: "Space Invaders",
+    roms: &INVADERS_ROMS,
+    color_proms: &[],
+    memory_map: &BOARD_MEMORY_MAP,
+    ports: &INVADERS_PORTS,
+    inputs: &INVADERS_INPUTS,
+    sounds: &INVADERS_SOUNDS,
+    dip_layout: DipLayout {
+        ships: [3, 4, 5, 6],
+        extra_ship: Some((3, [1500, 1000])),
+        coin_info: Some(7),
+    },
+};
+
+pub const INVADERS_ROMS: [RomChip; 4] = [
+    RomChip { name: "invaders.h", addr: 0x0000, size: 0x0800, crc: 0x734F5AD8 },
+    RomChip { name: "invaders.g", addr: 0x0800, size: 0x0800, crc: 0x6BFACA4A },
+    RomChip { name: "invaders.f", addr: 0x1000, size: 0x0800, crc: 0x0CCEAD96 },
+    RomChip { name: "invaders.e", addr: 0x1800, size: 0x0800, crc: 0x14E538B0 },
+];
+
+// IN 0-2   Cabinet inputs
+// IN 3     Shift register result
+// OUT 2    Shift register offset
+// OUT 3    Sound latch 1
+// OUT 4    Shift register data
+// OUT 5    Sound latch 2
+// OUT 6    Watchdog
+pub const INVADERS_PORTS: PortLayout = PortLayout {
+    reads: &[
+        (0, PortRead::Input(0)),
+        (1, PortRead::Input(1)),
+        (2, PortRead::Input(2)),
+        (3, PortRead::ShiftResult),
+    ],
+    writes: &[
+        (2, PortWrite::ShiftOffset),
+        (3, PortWrite::Sound(0)),
+        (4, PortWrite::ShiftData),
+        (5, PortWrite::Sound(1)),
+        (6, PortWrite::Watchdog),
+    ],
+};
+
+// The cabinet controls are read through input ports 0, 1 and 2. A bit is set
+// while its control is active. The layout is as follows:
+//
+// Port 0
+//   bit 1-3  Always 1
+//   bit 4    Fire      (wired to the same controls as player 1)
+//   bit 5    Left
+//   bit 6    Right
+//
+// Port 1
+//   bit 0    Coin
+//   bit 1    P2 Start
+//   bit 2    P1 Start
+//   bit 3    Always 1
+//   bit 4    P1 Fire
+//   bit 5    P1 Left
+//   bit 6    P1 Right
+//
+// Port 2
+//   bit 0-1  DIP switches
+//   bit 2    Tilt
+//   bit 3    DIP switches
+//   bit 4    P2 Fire
+//   bit 5    P2 Left
+//   bit 6    P2 Right
+//   bit 7    DIP switches
+pub const INVADERS_INPUTS: InputLayout = InputLayout {
+    defaults: [0b0000_1110, 0b0000_1000, 0b0000_0000],
+    buttons: &[
+        (Coin, 1, 0),
+        (P2Start, 1, 1),
+        (P1Start, 1, 2),
+        (P1Fire, 0, 4),
+        (P1Fire, 1, 4),
+        (P1Left, 0, 5),
+        (P1Left, 1, 5),
+        (P1Right, 0, 6),
+        (P1Right, 1, 6),
+        (Tilt, 2, 2),
+        (P2Fire, 2, 4),
+        (P2Left, 2, 5),
+        (P2Right, 2, 6),
+    ],
+};
+
+// Sound latch 1 (OUT 3)
+//   bit 0    UFO, looping while set
+//   bit 1    Shot
+//   bit 2    Player death
+//   bit 3    Invader death
+//   bit 4    Extra ship
+//
+// Sound latch 2 (OUT 5)
+//   bit 0-3  Fleet movement, one note per bit
+//   bit 4    UFO hit
+pub const INVADERS_SOUNDS: SoundLayout = SoundLayout {
+    bits: [
+        [Some(UFO_SOUND), Some(SHOT_SOUND), Some(PLAYER_DIE_SOUND), Some(INVADER_DIE_SOUND), Some(EXTRA_SHIP_SOUND), None, None, None],
+        [Some(FLEET_SOUNDS[0]), Some(FLEET_SOUNDS[1]), Some(FLEET_SOUNDS[2]), Some(FLEET_SOUNDS[3]), Some(UFO_HIT_SOUND), None, None, None],
+    ],
+    looping: Some(UFO_SOUND),
+};
@@ -0,0 +1,38 @@
+use crate::dip_switches::*;
+use crate::rom::*;
+
+use super::*;
+
+// Space Invaders Part II runs on the colour version of the board, with a
+// fifth program ROM at 0x4000 and PROMs holding the colour of each 8x8 cell
+pub static INVADPT2: GameDriver = GameDriver {
+    name: "invadpt2",
+    description: "Space Invaders Part II",
+    roms: &INVADPT2_ROMS,
+    color_proms: &INVADPT2_COLOR_PROMS,
+    memory_map: &BOARD_MEMORY_MAP,
+    ports: &COLOR_BOARD_PORTS,
+    // The same controls as Space Invaders
+    inputs: &invaders::INVADERS_INPUTS,
+    // Part II kept the Space Invaders sound board
+    sounds: &invaders::INVADERS_SOUNDS,
+    dip_layout: DipLayout {
+        // Only bit 0 selects the ship count
+        ships: [3, 4, 3, 4],
+        extra_ship: None,
+        coin_info: Some(7),
+    },
+};
+
+pub const INVADPT2_ROMS: [RomChip; 5] = [
+    RomChip { name: "pv01", addr: 0x0000, size: 0x0800, crc: 0x7288A511 },
+    RomChip { name: "pv02", addr: 0x0800, size: 0x0800, crc: 0x097DD8D5 },
+    RomChip { name: "pv03", addr: 0x1000, size: 0x0800, crc: 0x1766337E },
+    RomChip { name: "pv04", addr: 0x1800, size: 0x0800, crc: 0x8F0E62E0 },
+    RomChip { name: "pv05", addr: 0x4000, size: 0x0800, crc: 0x19B505E9 },
+];
+
+pub const INVADPT2_COLOR_PROMS: [RomChip; 2] = [
+    RomChip { name: "pv06.1", addr: 0x0000, size: 0x0400, crc: 0xA732810B },
+    RomChip { name: "pv07.2", addr: 0x0400, size: 0x0400, crc: 0x2C5B91CB },
+];
@@ -0,0 +1,37 @@
+use crate::dip_switches::*;
+use crate::rom::*;
+
+use super::*;
+
+// Lunar Rescue has two extra program ROMs at 0x4000 and a colour PROM
+pub static LRESCUE: GameDriver = GameDriver {
+    name: "lrescue",
+    description: "Lunar Rescue",
+    roms: &LRESCUE_ROMS,
+    color_proms: &LRESCUE_COLOR_PROMS,
+    memory_map: &BOARD_MEMORY_MAP,
+    ports: &COLOR_BOARD_PORTS,
+    // The same controls as Space Invaders
+    inputs: &invaders::INVADERS_INPUTS,
+    // Lunar Rescue's own sample set isn't supported, so it plays the Space
+    // Invaders sounds on the same bits
+    sounds: &invaders::INVADERS_SOUNDS,
+    dip_layout: DipLayout {
+        ships: [3, 4, 5, 6],
+        extra_ship: None,
+        coin_info: Some(7),
+    },
+};
+
+pub const LRESCUE_ROMS: [RomChip; 6] = [
+    RomChip { name: "lrescue.1", addr: 0x0000, size: 0x0800, crc: 0x2BBC4778 },
+    RomChip { name: "lrescue.2", addr: 0x0800, size: 0x0800, crc: 0x49E79706 },
+    RomChip { name: "lrescue.3", addr: 0x1000, size: 0x0800, crc: 0x1AC969BE },
+    RomChip { name: "lrescue.4", addr: 0x1800, size: 0x0800, crc: 0x782FEE3C },
+    RomChip { name: "lrescue.5", addr: 0x4000, size: 0x0800, crc: 0x58FDE8BC },
+    RomChip { name: "lrescue.6", addr: 0x4800, size: 0x0800, crc: 0xBFB0F65D },
+];
+
+pub const LRESCUE_COLOR_PROMS: [RomChip; 1] = [
+    RomChip { name: "7643-1.cpu", addr: 0x0000, size: 0x0400, crc: 0x8B3E4ECE },
+];
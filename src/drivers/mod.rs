@@ -0,0 +1,136 @@
+pub mod ballbomb;
+pub mod invaders;
+pub mod invadpt2;
+pub mod lrescue;
+
+use crate::constants::*;
+use crate::dip_switches::*;
+use crate::input::*;
+use crate::memory_map::*;
+use crate::rom::*;
+use crate::sound::*;
+
+use self::ballbomb::*;
+use self::invaders::*;
+use self::invadpt2::*;
+use self::lrescue::*;
+
+// What reading a port does on a particular board
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PortRead {
+    // One of the three cabinet input ports (see InputLayout)
+    Input(Byte),
+    ShiftResult,
+}
+
+// What writing a port does on a particular board
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PortWrite {
+    ShiftOffset,
+    ShiftData,
+    // One of the two sound latches (see SoundLayout)
+    Sound(usize),
+    // The colour boards turn the whole screen red while this bit is set,
+    // for when the player is hit
+    ScreenRed(usize),
+    Watchdog,
+}
+
+// How a game decodes the CPU's ports. A port can drive more than one piece
+// of hardware, and anything not listed is unconnected
+#[derive(Debug)]
+pub struct PortLayout {
+    pub reads: &'static [(Byte, PortRead)],
+    pub writes: &'static [(Byte, PortWrite)],
+}
+
+// Midway built a number of games on the same 8080 board as Space Invaders.
+// They share the CPU, video hardware and shift register but differ in
+// everything described here
+pub struct GameDriver {
+    // The MAME short name, used to select the game and to name its ROM set
+    pub name: &'static str,
+    pub description: &'static str,
+
+    pub roms: &'static [RomChip],
+    // Colour PROMs are not in the CPU's address space, so each chip's addr
+    // is its offset into the PROM data instead
+    pub color_proms: &'static [RomChip],
+
+    pub memory_map: &'static [MemoryRegion],
+    pub ports: &'static PortLayout,
+    pub inputs: &'static InputLayout,
+    pub sounds: &'static SoundLayout,
+    pub dip_layout: DipLayout,
+}
+
+pub static DRIVERS: [&GameDriver; 4] = [
+    &INVADERS,
+    &INVADPT2,
+    &LRESCUE,
+    &BALLBOMB,
+];
+
+pub fn find_driver(name: &str) -> Option<&'static GameDriver> {
+    DRIVERS.iter().find(|driver| driver.name == name).copied()
+}
+
+// Every game on the board decodes 15 address lines, ignoring A15, so
+// 0x8000 - 0xFFFF repeats the map below it. RAM is mirrored at 0x6000, and
+// 0x4000 - 0x5FFF holds the extra program ROMs of the later games. Space
+// Invaders leaves those sockets empty, but writes there are still dropped
+// like any other ROM write
+pub const BOARD_MEMORY_MAP: [MemoryRegion; 6] = [
+    MemoryRegion::rom(0x0000, 0x1FFF),
+    MemoryRegion::ram(0x2000, 0x23FF),
+    MemoryRegion::vram(0x2400, 0x3FFF),
+    MemoryRegion::rom(0x4000, 0x5FFF),
+    MemoryRegion::mirror(0x6000, 0x7FFF, 0x2000, 0x1FFF),
+    MemoryRegion::mirror(0x8000, 0xFFFF, 0x0000, 0x7FFF),
+];
+
+// The colour boards are wired like Space Invaders, except that OUT 3 bit 2,
+// which plays the sound of the player being hit, also turns the screen red
+pub const COLOR_BOARD_PORTS: PortLayout = PortLayout {
+    reads: &[
+        (0, PortRead::Input(0)),
+        (1, PortRead::Input(1)),
+        (2, PortRead::Input(2)),
+        (3, PortRead::ShiftResult),
+    ],
+    writes: &[
+        (2, PortWrite::ShiftOffset),
+        (3, PortWrite::Sound(0)),
+        (3, PortWrite::ScreenRed(2)),
+        (4, PortWrite::ShiftData),
+        (5, PortWrite::Sound(1)),
+        (6, PortWrite::Watchdog),
+    ],
+};
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_driver() {
+        assert_eq!(find_driver("invaders").unwrap().description, "Space Invaders");
+        assert_eq!(find_driver("lrescue").unwrap().name, "lrescue");
+        assert!(find_driver("pacman").is_none());
+    }
+
+    #[test]
+    fn test_roms_fit_memory_map() {
+        // Every program ROM should land in a ROM region of its driver's memory map
+        for driver in DRIVERS {
+            for chip in driver.roms {
+                let end = chip.addr + (chip.size as Word - 1);
+                let in_rom = driver.memory_map.iter()
+                    .any(|region| region.kind == RegionKind::Rom && region.contains(chip.addr) && region.contains(end));
+
+                assert!(in_rom, "{} {} is not loaded into ROM", driver.name, chip.name);
+            }
+        }
+    }
+}
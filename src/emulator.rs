@@ -1,37 +1,340 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
 use crate::bus::*;
 use crate::constants::*;
 use crate::cpu::*;
+use crate::dip_switches::*;
+use crate::drivers::*;
+use crate::input::*;
+use crate::machine::*;
+use crate::overlay::*;
+use crate::rom::*;
+use crate::sound::*;
+use crate::tracer::*;
+use crate::video::*;
+use crate::wav::*;
+
+// Why the emulator stopped running
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RunResult {
+    FrameComplete,
+    Breakpoint(Word),
+
+    // Emulation stops once whatever set off the watchpoint, or wrote to ROM
+    // under the Trap policy, has finished
+    Watchpoint { by: AccessBy, hit: WatchHit },
+    RomWrite { by: AccessBy, write: RomWrite },
+}
+
+// What the CPU was doing when it touched memory
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AccessBy {
+    Instruction(Word),
+    // Servicing an interrupt pushes the address it interrupted, pc
+    Interrupt { rst_vector: Byte, pc: Word },
+}
+
+impl fmt::Display for AccessBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessBy::Instruction(pc) => write!(f, "the instruction at {:04X}", pc),
+            AccessBy::Interrupt { rst_vector, pc } => write!(f, "the RST {} interrupt at {:04X}", rst_vector, pc),
+        }
+    }
+}
 
 pub struct Emulator {
     cpu: Cpu,
+    machine: Machine,
+    video: Video,
     paused: bool,
+
+    // The audio mixed during the last frame, and where it is being recorded to.
+    // Recording follows emulated frames rather than the host's audio device,
+    // so the same inputs always record the same audio
+    audio: Vec<i16>,
+    audio_recorder: Option<WavWriter>,
+
+    // Frames run since starting, for timestamping events
+    frame_count: u64,
+
+    // Cycles executed so far in the current frame. Instructions don't line up
+    // exactly with the end of a frame, so any overshoot carries into the next one
+    frame_cycles: usize,
+
+    // Addresses to stop at before executing, plus a one-off stop used to run
+    // to an address. Resuming from a stop must not hit the same one again,
+    // so remember where we stopped
+    breakpoints: HashSet<Word>,
+    run_to: Option<Word>,
+    stopped_at: Option<Word>,
 }
 
 impl Emulator {
 
-    pub fn new() -> Emulator {
-        let mut bus = Bus::new();
-        bus.load_rom();
+    pub fn new(driver: &'static GameDriver, rom_set: &RomSet, dip_switches: DipSwitches, sound: Sound, overlay: Overlay, rom_write_policy: RomWritePolicy) -> Result<Emulator, String> {
+        let dip_bits = driver.dip_layout.port_bits(&dip_switches)?;
+
+        let mut bus = Bus::with_memory_map(driver.memory_map, rom_write_policy);
+        bus.load_rom(rom_set);
 
         let mut cpu = Cpu::new(bus);
         cpu.reset();
 
-        Emulator {
+        Ok(Emulator {
             cpu: cpu,
+            machine: Machine::new(driver, dip_bits, sound),
+            video: Video::new(overlay),
             paused: false,
+            frame_count: 0,
+            audio: vec![0; SAMPLES_PER_FRAME],
+            audio_recorder: None,
+            frame_cycles: 0,
+            breakpoints: HashSet::new(),
+            run_to: None,
+            stopped_at: None,
+        })
+    }
+
+    // Runs until the end of the current frame, or until a breakpoint is reached
+    pub fn run(&mut self) -> RunResult {
+        if self.paused {
+            return RunResult::FrameComplete;
+        }
+
+        let frame = self.frame_count;
+
+        while self.frame_count == frame {
+            let pc = self.cpu.program_counter();
+            let resuming = self.stopped_at.take() == Some(pc);
+
+            if !resuming && !self.cpu.is_halted() && self.is_breakpoint(pc) {
+                self.run_to = None;
+                self.stopped_at = Some(pc);
+                return RunResult::Breakpoint(pc);
+            }
+
+            let execution = self.execute_instruction();
+
+            if let Some(result) = self.check_memory_stop(pc, execution) {
+                return result;
+            }
         }
+
+        RunResult::FrameComplete
+    }
+
+    // Executes a single instruction for the debugger, which then counts as
+    // stopped at the next one. Returns any watchpoint or ROM write it set off
+    pub fn step(&mut self) -> Option<RunResult> {
+        let pc = self.cpu.program_counter();
+        let execution = self.execute_instruction();
+        self.stopped_at = Some(self.cpu.program_counter());
+
+        self.check_memory_stop(pc, execution)
     }
 
-    pub fn run(&mut self) {
-        let mut frame_cycles = 0;
+    // Collects a trapped ROM write or watchpoint hit from the last execution,
+    // which started at pc. Like a breakpoint, resuming must not stop again
+    // where it left off
+    fn check_memory_stop(&mut self, pc: Word, execution: Execution) -> Option<RunResult> {
+        let by = match execution.interrupt {
+            Some(rst_vector) => AccessBy::Interrupt { rst_vector: rst_vector, pc: pc },
+            None => AccessBy::Instruction(pc),
+        };
+
+        let write = self.cpu.bus_mut().take_rom_write();
+        let hit = self.cpu.bus().take_watch_hit();
+
+        let result = match (write, hit) {
+            (Some(write), _) => RunResult::RomWrite { by: by, write: write },
+            (None, Some(hit)) => RunResult::Watchpoint { by: by, hit: hit },
+            (None, None) => return None,
+        };
+
+        self.run_to = None;
+        self.stopped_at = Some(self.cpu.program_counter());
 
-        if !self.paused {
-            while frame_cycles < MAX_CYCLES_PER_FRAME {
-                let cycles = self.cpu.execute();
-                frame_cycles += cycles as usize;
+        Some(result)
+    }
+
+    fn execute_instruction(&mut self) -> Execution {
+        // A halted CPU still reports its idle cycles, so the
+        // frame keeps advancing while it waits for an interrupt
+        let execution = self.cpu.execute(&mut self.machine);
+
+        let previous_cycles = self.frame_cycles;
+        self.frame_cycles += execution.cycles as usize;
+
+        let reached = |target: usize| previous_cycles < target && self.frame_cycles >= target;
+
+        if reached(MID_SCREEN_CYCLES) {
+            self.cpu.request_interrupt(MID_SCREEN_INTERRUPT);
+        }
 
-                // self.cpu.handle_interrupts();
+        if reached(VBLANK_CYCLES) {
+            self.cpu.request_interrupt(VBLANK_INTERRUPT);
+            self.video.update(self.cpu.bus(), self.machine.screen_red());
+        }
+
+        if self.frame_cycles >= MAX_CYCLES_PER_FRAME {
+            self.frame_cycles -= MAX_CYCLES_PER_FRAME;
+            self.end_frame();
+        }
+
+        execution
+    }
+
+    fn end_frame(&mut self) {
+        self.machine.render_audio(&mut self.audio);
+
+        if self.machine.tick_watchdog() {
+            eprintln!("Frame {}: watchdog expired at PC {:04X}, resetting the board", self.frame_count, self.cpu.program_counter());
+            self.cpu.reset();
+            self.machine.reset();
+        }
+
+        if let Some(recorder) = &mut self.audio_recorder {
+            if let Err(e) = recorder.write(&self.audio) {
+                eprintln!("{}, audio recording stopped", e);
+                self.audio_recorder = None;
             }
         }
+
+        self.frame_count += 1;
+    }
+
+    fn is_breakpoint(&self, addr: Word) -> bool {
+        self.run_to == Some(addr) || self.breakpoints.contains(&addr)
+    }
+
+    pub fn add_breakpoint(&mut self, addr: Word) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: Word) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> Vec<Word> {
+        let mut breakpoints: Vec<Word> = self.breakpoints.iter().copied().collect();
+        breakpoints.sort();
+        breakpoints
+    }
+
+    // Stops the next time the CPU reaches addr, as well as at any breakpoints
+    pub fn run_to(&mut self, addr: Word) {
+        self.run_to = Some(addr);
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn enable_watchdog(&mut self, timeout: usize) {
+        self.machine.enable_watchdog(timeout);
+    }
+
+    pub fn record_audio(&mut self, path: &Path) -> Result<(), String> {
+        self.audio_recorder = Some(WavWriter::create(path)?);
+        Ok(())
+    }
+
+    pub fn trace(&mut self, tracer: Tracer) {
+        self.cpu.set_tracer(Some(tracer));
+    }
+
+    pub fn flush_trace(&mut self) -> Result<(), String> {
+        match self.cpu.tracer_mut() {
+            Some(tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn finish_recording(&mut self) -> Result<(), String> {
+        match self.audio_recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_input(&mut self, button: CabinetButton, pressed: bool) {
+        self.machine.set_input(button, pressed);
+    }
+
+    pub fn framebuffer(&self) -> &[Byte] {
+        self.video.framebuffer()
+    }
+
+    pub fn audio(&self) -> &[i16] {
+        &self.audio
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::invaders::*;
+
+    fn test_emulator(program: &[Byte], rom_write_policy: RomWritePolicy) -> Emulator {
+        let chip = RomChip { name: "test", addr: 0x0000, size: program.len(), crc: 0 };
+        let rom_set = RomSet { chips: vec![(chip, program.to_vec())] };
+
+        Emulator::new(&INVADERS, &rom_set, DipSwitches::new(), Sound::silent(INVADERS.sounds), Overlay::none(), rom_write_policy).unwrap()
+    }
+
+    #[test]
+    fn test_interrupt_timing() {
+        // EI then loop on JMP 1, with each RST handler doing the same
+        let mut program = vec![0x00; 0x14];
+        for addr in [0x0000, 0x0008, 0x0010] {
+            program[addr..addr + 4].copy_from_slice(&[0xFB, 0xC3, 0x01, 0x00]);
+        }
+
+        let mut emulator = test_emulator(&program, RomWritePolicy::Ignore);
+        emulator.add_breakpoint(0x0008);
+        emulator.add_breakpoint(0x0010);
+
+        // Each interrupt is requested by the first instruction to reach its
+        // cycle count and serviced straight after, so it lands within one
+        // JMP (10 cycles) and the RST (11 cycles) of it
+        let lands_at = |emulator: &Emulator, target: usize| {
+            (target + 11..target + 21).contains(&emulator.frame_cycles)
+        };
+
+        for frame in 0..2 {
+            assert_eq!(emulator.run(), RunResult::Breakpoint(0x0008));
+            assert!(lands_at(&emulator, MID_SCREEN_CYCLES), "RST 1 at {} cycles", emulator.frame_cycles);
+
+            assert_eq!(emulator.run(), RunResult::Breakpoint(0x0010));
+            assert!(lands_at(&emulator, VBLANK_CYCLES), "RST 2 at {} cycles", emulator.frame_cycles);
+            assert_eq!(emulator.frame_count(), frame);
+
+            assert_eq!(emulator.run(), RunResult::FrameComplete);
+            assert_eq!(emulator.frame_count(), frame + 1);
+        }
+    }
+    #[test]
+    fn test_rom_write_trap() {
+        // STA 0010H, which is in ROM, then JMP 0
+        let mut emulator = test_emulator(&[0x32, 0x10, 0x00, 0xC3, 0x00, 0x00], RomWritePolicy::Trap);
+
+        let write = RomWrite { addr: 0x0010, data: 0x00 };
+        assert_eq!(emulator.run(), RunResult::RomWrite { by: AccessBy::Instruction(0x0000), write: write });
+        assert_eq!(emulator.cpu().program_counter(), 0x0003);
+
+        // Running on carries on from the next instruction
+        assert!(matches!(emulator.run(), RunResult::RomWrite { .. }));
+        assert_eq!(emulator.cpu().program_counter(), 0x0003);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::constants::*;
+use crate::utils::*;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum CabinetButton {
+    Coin,
+    P1Start,
+    P2Start,
+    P1Fire,
+    P1Left,
+    P1Right,
+    P2Fire,
+    P2Left,
+    P2Right,
+    Tilt,
+}
+
+// How a game wires the cabinet controls to input ports 0, 1 and 2
+#[derive(Debug)]
+pub struct InputLayout {
+    // The value of each port with no controls active
+    pub defaults: [Byte; 3],
+
+    // The port and bit of each control. A bit is set while its control is
+    // active, and a control can be wired to more than one bit
+    pub buttons: &'static [(CabinetButton, Byte, usize)],
+}
+
+pub struct Inputs {
+    layout: &'static InputLayout,
+    ports: [Byte; 3],
+
+    // The DIP switch bits read through port 2 (see DipLayout)
+    dip_bits: Byte,
+}
+
+impl Inputs {
+
+    pub fn new(layout: &'static InputLayout, dip_bits: Byte) -> Inputs {
+        Inputs {
+            layout: layout,
+            ports: layout.defaults,
+            dip_bits: dip_bits,
+        }
+    }
+
+    pub fn set_button(&mut self, button: CabinetButton, pressed: bool) {
+        let update = if pressed { set_bit } else { reset_bit };
+
+        for &(_, port, bit) in self.layout.buttons.iter().filter(|(b, _, _)| *b == button) {
+            update(&mut self.ports[port as usize], bit);
+        }
+    }
+
+    pub fn read_port(&self, port: Byte) -> Byte {
+        match port {
+            0 | 1 => self.ports[port as usize],
+            2 => self.ports[2] | self.dip_bits,
+            _ => panic!("Port {} is not an input port", port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dip_switches::*;
+    use crate::drivers::invaders::*;
+
+    #[test]
+    fn test_set_button() {
+        let mut inputs = Inputs::new(&INVADERS_INPUTS, 0);
+
+        inputs.set_button(CabinetButton::Coin, true);
+        inputs.set_button(CabinetButton::P1Left, true);
+        inputs.set_button(CabinetButton::P2Fire, true);
+
+        assert_eq!(inputs.read_port(0), 0b0010_1110);
+        assert_eq!(inputs.read_port(1), 0b0010_1001);
+        assert_eq!(inputs.read_port(2), 0b0001_0000);
+
+        inputs.set_button(CabinetButton::Coin, false);
+        inputs.set_button(CabinetButton::P1Left, false);
+
+        assert_eq!(inputs.read_port(0), 0b0000_1110);
+        assert_eq!(inputs.read_port(1), 0b0000_1000);
+        assert_eq!(inputs.read_port(2), 0b0001_0000);
+    }
+
+    #[test]
+    fn test_dip_switches() {
+        let mut dip_switches = DipSwitches::new();
+        dip_switches.set_ships(5).unwrap();
+        dip_switches.coin_info = false;
+
+        let dip_bits = INVADERS.dip_layout.port_bits(&dip_switches).unwrap();
+        let mut inputs = Inputs::new(&INVADERS_INPUTS, dip_bits);
+        inputs.set_button(CabinetButton::Tilt, true);
+
+        assert_eq!(inputs.read_port(2), 0b1000_0110);
+    }
+}
@@ -0,0 +1,9 @@
+use crate::constants::*;
+
+// The 8080 has a separate 256 port I/O address space reached through the IN and
+// OUT instructions. Whatever hardware is wired to those ports is machine specific,
+// so the CPU only talks to it through this trait
+pub trait IoPorts {
+    fn read_port(&mut self, port: Byte) -> Byte;
+    fn write_port(&mut self, port: Byte, data: Byte);
+}
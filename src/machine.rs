@@ -0,0 +1,175 @@
+use crate::constants::*;
+use crate::drivers::*;
+use crate::input::*;
+use crate::io::*;
+use crate::shift_register::*;
+use crate::sound::*;
+use crate::utils::*;
+use crate::watchdog::*;
+
+// The I/O hardware on the board, as seen through the CPU's ports
+pub struct Machine {
+    ports: &'static PortLayout,
+    inputs: Inputs,
+    shift_register: ShiftRegister,
+    sound: Sound,
+    watchdog: Option<Watchdog>,
+    screen_red: bool,
+}
+
+impl Machine {
+
+    pub fn new(driver: &'static GameDriver, dip_bits: Byte, sound: Sound) -> Machine {
+        Machine {
+            ports: driver.ports,
+            inputs: Inputs::new(driver.inputs, dip_bits),
+            shift_register: ShiftRegister::new(),
+            sound: sound,
+            watchdog: None,
+            screen_red: false,
+        }
+    }
+
+    // A watchdog reset clears everything latched on the board. The controls
+    // and DIP switches aren't latched, so they keep their state
+    pub fn reset(&mut self) {
+        self.shift_register = ShiftRegister::new();
+        self.sound.reset();
+        self.screen_red = false;
+    }
+
+    pub fn enable_watchdog(&mut self, timeout: usize) {
+        self.watchdog = Some(Watchdog::new(timeout));
+    }
+
+    // Advances the watchdog by a frame, returning true if it has expired
+    pub fn tick_watchdog(&mut self) -> bool {
+        match &mut self.watchdog {
+            Some(watchdog) => watchdog.tick(),
+            None => false,
+        }
+    }
+
+    pub fn set_input(&mut self, button: CabinetButton, pressed: bool) {
+        self.inputs.set_button(button, pressed);
+    }
+
+    pub fn screen_red(&self) -> bool {
+        self.screen_red
+    }
+
+    pub fn render_audio(&mut self, buffer: &mut [i16]) {
+        self.sound.render(buffer);
+    }
+}
+
+impl IoPorts for Machine {
+
+    fn read_port(&mut self, port: Byte) -> Byte {
+        let handler = self.ports.reads.iter().find(|(p, _)| *p == port).map(|(_, handler)| *handler);
+
+        match handler {
+            Some(PortRead::Input(input)) => self.inputs.read_port(input),
+            Some(PortRead::ShiftResult) => self.shift_register.read_result(),
+            // Nothing drives an unconnected port, so it reads back as 0
+            None => 0
+        }
+    }
+
+    fn write_port(&mut self, port: Byte, data: Byte) {
+        for &(_, handler) in self.ports.writes.iter().filter(|(p, _)| *p == port) {
+            match handler {
+                PortWrite::ShiftOffset => self.shift_register.write_offset(data),
+                PortWrite::ShiftData => self.shift_register.write_data(data),
+                PortWrite::Sound(latch) => self.sound.write_port(latch, data),
+                PortWrite::ScreenRed(bit) => self.screen_red = is_bit_set(&data, bit),
+                PortWrite::Watchdog => {
+                    if let Some(watchdog) = &mut self.watchdog {
+                        watchdog.kick();
+                    }
+                },
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ballbomb::*;
+    use crate::drivers::invaders::*;
+    use crate::drivers::invadpt2::*;
+
+    fn test_machine(driver: &'static GameDriver) -> Machine {
+        // Each sound is a single sample of its own number + 1
+        let samples = (0..SOUND_COUNT).map(|sound| vec![sound as i16 + 1]).collect();
+        Machine::new(driver, 0, Sound::new(driver.sounds, samples))
+    }
+
+    #[test]
+    fn test_shared_ports() {
+        for driver in [&INVADERS, &INVADPT2, &BALLBOMB] {
+            let mut machine = test_machine(driver);
+
+            machine.write_port(4, 0xAB);
+            machine.write_port(4, 0xCD);
+            machine.write_port(2, 4);
+            assert_eq!(machine.read_port(3), 0xDA, "{}", driver.name);
+            assert_eq!(machine.read_port(1), 0b0000_1000, "{}", driver.name);
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut machine = test_machine(&INVADPT2);
+        let mut buffer = [0; 2];
+
+        machine.write_port(4, 0xAB);
+        machine.write_port(2, 4);
+        machine.write_port(3, 0b0000_0101);
+        machine.reset();
+
+        assert_eq!(machine.read_port(3), 0x00);
+        assert!(!machine.screen_red());
+        machine.render_audio(&mut buffer);
+        assert_eq!(buffer, [0, 0]);
+
+        // The cleared latches see the sound bits rise again
+        machine.write_port(3, 0b0000_0001);
+        machine.render_audio(&mut buffer);
+        assert_eq!(buffer, [UFO_SOUND as i16 + 1; 2]);
+    }
+
+    #[test]
+    fn test_screen_red() {
+        // OUT 3 bit 2 is the player death sound on both boards, but only the
+        // colour board also turns the screen red
+        let mut invaders = test_machine(&INVADERS);
+        let mut invadpt2 = test_machine(&INVADPT2);
+
+        invaders.write_port(3, 0b0000_0100);
+        invadpt2.write_port(3, 0b0000_0100);
+        assert!(!invaders.screen_red());
+        assert!(invadpt2.screen_red());
+
+        invadpt2.write_port(3, 0b0000_0000);
+        assert!(!invadpt2.screen_red());
+    }
+
+    #[test]
+    fn test_sound_wiring() {
+        // OUT 3 bit 0 starts the looping UFO on Space Invaders, and a balloon
+        // being hit on Balloon Bomber
+        let mut invaders = test_machine(&INVADERS);
+        let mut ballbomb = test_machine(&BALLBOMB);
+        let mut buffer = [0; 2];
+
+        invaders.write_port(3, 0b0000_0001);
+        invaders.render_audio(&mut buffer);
+        assert_eq!(buffer, [UFO_SOUND as i16 + 1; 2]);
+
+        ballbomb.write_port(3, 0b0000_0001);
+        ballbomb.render_audio(&mut buffer);
+        assert_eq!(buffer, [INVADER_DIE_SOUND as i16 + 1, 0]);
+    }
+}
@@ -1,10 +1,36 @@
 pub mod bus;
+pub mod code_map;
+pub mod config;
 pub mod constants;
 pub mod cpu;
+pub mod debugger;
+pub mod disassembler;
+pub mod dip_switches;
+pub mod drivers;
 pub mod emulator;
+pub mod input;
+pub mod io;
+pub mod machine;
+pub mod memory_map;
 pub mod ops;
+pub mod overlay;
+pub mod rom;
+pub mod shift_register;
+pub mod sound;
+pub mod synth;
+pub mod tracer;
 pub mod utils;
+pub mod video;
+pub mod watchdog;
+pub mod wav;
 
+use std::collections::HashMap;
+use std::fs;
+use std::io::{stdin, stdout};
+use std::process;
+
+use clap::Parser;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::event::Event;
 use sdl2::EventPump;
 use sdl2::keyboard::Keycode;
@@ -12,16 +38,141 @@ use sdl2::pixels::Color;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::render::TextureCreator;
 
+use crate::bus::*;
+use crate::code_map::*;
+use crate::config::*;
 use crate::constants::*;
+use crate::debugger::*;
+use crate::disassembler::*;
+use crate::drivers::*;
 use crate::emulator::*;
+use crate::input::*;
+use crate::overlay::*;
+use crate::rom::*;
+use crate::sound::*;
+use crate::tracer::*;
 
 fn main() {
 
+    let options = Options::parse();
+    let driver = find_driver(&options.game).unwrap_or_else(|| {
+        let names: Vec<&str> = DRIVERS.iter().map(|driver| driver.name).collect();
+        eprintln!("Unknown game {}, expected one of {}", options.game, names.join(", "));
+        process::exit(1);
+    });
+
+    let dip_switches = options.dip_switches().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+
+    // The colour PROMs are loaded along with the program ROMs when their
+    // overlay is wanted, as a single image holds both
+    let load_proms = options.overlay_file.is_none() && options.overlay == OverlayMode::Prom && !driver.color_proms.is_empty();
+
+    let roms = match load_proms {
+        true => RomSet::load_with_proms(&options.rom, driver.roms, driver.color_proms)
+            .map(|(rom_set, prom_set)| (rom_set, Some(prom_set))),
+        false => RomSet::load(&options.rom, driver.roms).map(|rom_set| (rom_set, None)),
+    };
+
+    let (rom_set, prom_set) = roms.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    if options.disassemble {
+        let mut bus = Bus::with_memory_map(driver.memory_map, RomWritePolicy::Ignore);
+        bus.load_rom(&rom_set);
+
+        print!("{}", listing(&bus, &rom_set));
+        return;
+    }
+
+    if let Some(path) = &options.asm {
+        let mut bus = Bus::with_memory_map(driver.memory_map, RomWritePolicy::Ignore);
+        bus.load_rom(&rom_set);
+
+        let code_map = CodeMap::analyse(&bus, &rom_set.ranges(), &BOARD_ENTRY_POINTS);
+        let asm = code_map.to_asm(&bus, &format!("{} ({})", driver.description, driver.name));
+
+        fs::write(path, asm).unwrap_or_else(|e| {
+            eprintln!("{} could not be written: {}", path.display(), e);
+            process::exit(1);
+        });
+        return;
+    }
+
+    let overlay = match (&options.overlay_file, options.overlay) {
+        (Some(path), _) => Overlay::load(path),
+        (None, OverlayMode::None) => Ok(Overlay::none()),
+        (None, OverlayMode::Classic) => Ok(Overlay::from_bands(&CLASSIC_BANDS)),
+        (None, OverlayMode::Prom) => match &prom_set {
+            Some(prom_set) => Overlay::from_colour_prom(&prom_set.data()),
+            None => Err(format!("{} has no colour PROMs", driver.description)),
+        },
+    };
+
+    let overlay = overlay.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let sound = match options.sound {
+        SoundMode::Samples => Sound::load_samples(driver.sounds, &options.samples).unwrap_or_else(|e| {
+            eprintln!("Sound samples could not be loaded from {}, using synthesized sound instead:", options.samples.display());
+            for line in e.lines() {
+                eprintln!("  {}", line);
+            }
+            Sound::synthesized(driver.sounds)
+        }),
+        SoundMode::Synth => Sound::synthesized(driver.sounds),
+        SoundMode::Off => Sound::silent(driver.sounds),
+    };
+
+    let mut emulator = Emulator::new(driver, &rom_set, dip_switches, sound, overlay, options.rom_writes).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    if let Some(timeout) = options.watchdog {
+        emulator.enable_watchdog(timeout);
+    }
+
+    if let Some(path) = &options.record_audio {
+        emulator.record_audio(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+    }
+
+    if let Some(path) = &options.trace {
+        let tracer = Tracer::create(path, options.trace_filter()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        emulator.trace(tracer);
+    }
+
+    for addr in &options.breakpoints {
+        emulator.add_breakpoint(*addr);
+    }
+
+    for watchpoint in &options.watchpoints {
+        emulator.cpu_mut().bus_mut().add_watchpoint(*watchpoint);
+    }
+
+    if options.headless {
+        run_headless(&mut emulator, options.frames, options.debug);
+        finish(&mut emulator);
+        return;
+    }
+
     // Initialize SDL
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let window = video_subsystem
-        .window("Space Invaders", (DISPLAY_WIDTH * DISPLAY_FACTOR) as u32, (DISPLAY_HEIGHT * DISPLAY_FACTOR) as u32)
+        .window(driver.description, (DISPLAY_WIDTH * DISPLAY_FACTOR) as u32, (DISPLAY_HEIGHT * DISPLAY_FACTOR) as u32)
         .position_centered()
         .build().unwrap();
 
@@ -30,43 +181,148 @@ fn main() {
     let mut event_pump = sdl_context.event_pump().unwrap();
     canvas.set_scale(DISPLAY_FACTOR as f32, DISPLAY_FACTOR as f32).unwrap();
 
-    let mut creator = canvas.texture_creator();
+    let audio_queue: Option<AudioQueue<i16>> = match options.sound {
+        SoundMode::Off => None,
+        _ => {
+            let audio_subsystem = sdl_context.audio().unwrap();
+            let desired_spec = AudioSpecDesired {
+                freq: Some(SAMPLE_RATE as i32),
+                channels: Some(1),
+                samples: None,
+            };
+
+            audio_subsystem.open_queue(None, &desired_spec).ok()
+        }
+    };
+
+    if let Some(queue) = &audio_queue {
+        queue.resume();
+    }
+
+    let creator = canvas.texture_creator();
     let mut texture = creator
-        .create_texture_target(PixelFormatEnum::RGB24, DISPLAY_WIDTH, DISPLAY_HEIGHT).unwrap();
+        .create_texture_streaming(PixelFormatEnum::RGB24, DISPLAY_WIDTH, DISPLAY_HEIGHT).unwrap();
 
-    let mut emulator = Emulator::new();
+    let mut key_map = HashMap::new();
+    key_map.insert(Keycode::C, CabinetButton::Coin);
+    key_map.insert(Keycode::Num1, CabinetButton::P1Start);
+    key_map.insert(Keycode::Num2, CabinetButton::P2Start);
+    key_map.insert(Keycode::Space, CabinetButton::P1Fire);
+    key_map.insert(Keycode::Left, CabinetButton::P1Left);
+    key_map.insert(Keycode::Right, CabinetButton::P1Right);
+    key_map.insert(Keycode::K, CabinetButton::P2Fire);
+    key_map.insert(Keycode::J, CabinetButton::P2Left);
+    key_map.insert(Keycode::L, CabinetButton::P2Right);
+    key_map.insert(Keycode::T, CabinetButton::Tilt);
+
+    let mut debugging = options.debug;
 
     'running: loop {
-        emulator.run();
-        // texture.update(None, rusty_boy.get_screen(), 160 * 3).unwrap();
-
-        canvas.copy(&texture, None, None).unwrap();
-        canvas.present();
-
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} |
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    break 'running;
-                },
-                Event::KeyDown { keycode: Some(Keycode::P), .. } => {
-                    // rusty_boy.toggle_pause();
-                },
-                Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                    // rusty_boy.debug();
-                },
-                Event::KeyDown { keycode, .. } => {
-                    // if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                    //     rusty_boy.set_button_state(*key);
-                    // }
+        if debugging {
+            // The console blocks on stdin, so the window can't redraw or answer
+            // the window manager until it returns and may be shown as not
+            // responding. Handle anything already queued first, so a quit isn't
+            // lost and no button is left held down while stopped
+            if handle_events(&mut event_pump, &key_map, &mut emulator, &mut debugging) {
+                break 'running;
+            }
+
+            if run_console(&mut emulator, &mut stdin().lock(), &mut stdout()) == DebuggerAction::Quit {
+                break 'running;
+            }
+
+            debugging = false;
+        }
+
+        if report_stop(emulator.run()) {
+            // Stopping partway through a frame leaves nothing new to show or play
+            debugging = true;
+        } else {
+            texture.update(None, emulator.framebuffer(), (DISPLAY_WIDTH * 3) as usize).unwrap();
+
+            // Keep a couple of frames of audio queued, dropping a frame rather
+            // than letting the sound fall behind the picture
+            if let Some(queue) = &audio_queue {
+                if queue.size() < (SAMPLES_PER_FRAME * 2 * 2) as u32 {
+                    queue.queue(emulator.audio());
+                }
+            }
+
+            canvas.copy(&texture, None, None).unwrap();
+            canvas.present();
+        }
+
+        if handle_events(&mut event_pump, &key_map, &mut emulator, &mut debugging) {
+            break 'running;
+        }
+    }
+
+    finish(&mut emulator);
+}
+
+// Passes the window's events on to the emulator, returning true to quit
+fn handle_events(event_pump: &mut EventPump, key_map: &HashMap<Keycode, CabinetButton>, emulator: &mut Emulator, debugging: &mut bool) -> bool {
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit {..} |
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                return true;
+            },
+            Event::KeyDown { keycode: Some(Keycode::P), .. } => {
+                // rusty_boy.toggle_pause();
+            },
+            Event::KeyDown { keycode: Some(Keycode::D), .. } => {
+                *debugging = true;
+            },
+            Event::KeyDown { keycode, .. } => {
+                if let Some(button) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                    emulator.set_input(*button, true);
                 }
-                Event::KeyUp { keycode, .. } => {
-                    // if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                    //     rusty_boy.reset_button_state(*key);
-                    // }
-                },
-                _ => {}
             }
+            Event::KeyUp { keycode, .. } => {
+                if let Some(button) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                    emulator.set_input(*button, false);
+                }
+            },
+            _ => {}
         }
     }
+
+    false
+}
+
+// Runs without a window or sound device, for debugging and for recordings.
+// The debugger console takes commands from stdin
+fn run_headless(emulator: &mut Emulator, frames: Option<u64>, debug: bool) {
+    let mut debugging = debug;
+
+    while frames.is_none_or(|frames| emulator.frame_count() < frames) {
+        if debugging && run_console(emulator, &mut stdin().lock(), &mut stdout()) == DebuggerAction::Quit {
+            return;
+        }
+
+        debugging = report_stop(emulator.run());
+    }
+}
+
+// Prints why the emulator stopped before the end of a frame, returning true
+// if it did so the debugger console can be opened
+fn report_stop(result: RunResult) -> bool {
+    match describe_stop(result) {
+        Some(stop) => {
+            println!("{}", stop);
+            true
+        },
+        None => false,
+    }
+}
+
+fn finish(emulator: &mut Emulator) {
+    if let Err(e) = emulator.finish_recording() {
+        eprintln!("{}", e);
+    }
+
+    if let Err(e) = emulator.flush_trace() {
+        eprintln!("{}", e);
+    }
 }
@@ -0,0 +1,56 @@
+use crate::constants::*;
+
+// How a range of the address space behaves when the CPU accesses it
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RegionKind {
+    // Read only. Writes are handled according to the bus' RomWritePolicy
+    Rom,
+    // Read/write memory
+    Ram,
+    // Read/write memory that the video hardware scans out to the screen
+    Vram,
+    // Accesses are redirected to base | (addr & mask), for address lines the board doesn't decode
+    Mirror { base: Word, mask: Word },
+    // Nothing responds. Reads return whatever value floats on the data bus
+    // and writes are dropped
+    Unmapped { open_bus: Byte },
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct MemoryRegion {
+    pub start: Word,
+    pub end: Word,
+    pub kind: RegionKind,
+}
+
+impl MemoryRegion {
+
+    pub const fn rom(start: Word, end: Word) -> MemoryRegion {
+        MemoryRegion { start, end, kind: RegionKind::Rom }
+    }
+
+    pub const fn ram(start: Word, end: Word) -> MemoryRegion {
+        MemoryRegion { start, end, kind: RegionKind::Ram }
+    }
+
+    pub const fn vram(start: Word, end: Word) -> MemoryRegion {
+        MemoryRegion { start, end, kind: RegionKind::Vram }
+    }
+
+    pub const fn mirror(start: Word, end: Word, base: Word, mask: Word) -> MemoryRegion {
+        MemoryRegion { start, end, kind: RegionKind::Mirror { base, mask } }
+    }
+
+    pub const fn unmapped(start: Word, end: Word, open_bus: Byte) -> MemoryRegion {
+        MemoryRegion { start, end, kind: RegionKind::Unmapped { open_bus } }
+    }
+
+    pub fn contains(&self, addr: Word) -> bool {
+        addr >= self.start && addr <= self.end
+    }
+}
+
+// Without a board to describe, the whole address space is plain RAM
+pub const FLAT_MEMORY_MAP: [MemoryRegion; 1] = [
+    MemoryRegion::ram(0x0000, 0xFFFF),
+];
@@ -14,16 +14,38 @@ pub enum Operation {
     ADI,
     ANA,
     ANI,
+    CALL,
+    CC,
+    CM,
     CMA,
     CMC,
     CMP,
+    CNC,
+    CNZ,
+    CP,
+    CPE,
     CPI,
+    CPO,
+    CZ,
     DAA,
     DAD,
     DCR,
     DCX,
+    DI,
+    EI,
+    HLT,
+    IN,
     INR,
     INX,
+    JC,
+    JM,
+    JMP,
+    JNC,
+    JNZ,
+    JP,
+    JPE,
+    JPO,
+    JZ,
     LDA,
     LDAX,
     LHLD,
@@ -32,13 +54,24 @@ pub enum Operation {
     NOP,
     ORA,
     ORI,
+    OUT,
     PCHL,
     POP,
     PUSH,
     RAL,
     RAR,
+    RC,
+    RET,
     RLC,
+    RM,
+    RNC,
+    RNZ,
+    RP,
+    RPE,
+    RPO,
     RRC,
+    RST,
+    RZ,
     SBB,
     SBI,
     SHLD,
@@ -107,7 +140,7 @@ lazy_static! {
         OpCode::new(0x16, String::from("MVI D"), Operation::MVI, 2, 7, None),
         OpCode::new(0x17, String::from("RAL"), Operation::RAL, 1, 4, None),
         OpCode::new(0x19, String::from("DAD D"), Operation::DAD, 1, 10, None),
-        OpCode::new(0x1A, String::from("LDAX B"), Operation::LDAX, 1, 7, None),
+        OpCode::new(0x1A, String::from("LDAX D"), Operation::LDAX, 1, 7, None),
         OpCode::new(0x1B, String::from("DCX D"), Operation::DCX, 1, 5, None),
         OpCode::new(0x1C, String::from("INR E"), Operation::INR, 1, 5, None),
         OpCode::new(0x1D, String::from("DCR E"), Operation::DCR, 1, 5, None),
@@ -199,7 +232,7 @@ lazy_static! {
         OpCode::new(0x73, String::from("MOV M,E"), Operation::MOV, 1, 7, None),
         OpCode::new(0x74, String::from("MOV M,H"), Operation::MOV, 1, 7, None),
         OpCode::new(0x75, String::from("MOV M,L"), Operation::MOV, 1, 7, None),
-        // OpCode::new(0x76, String::from("MOV B,M"), Operation::MOV, 1, 7, None),
+        OpCode::new(0x76, String::from("HLT"), Operation::HLT, 1, 7, None),
         OpCode::new(0x77, String::from("MOV M,A"), Operation::MOV, 1, 7, None),
         OpCode::new(0x78, String::from("MOV A,B"), Operation::MOV, 1, 5, None),
         OpCode::new(0x79, String::from("MOV A,C"), Operation::MOV, 1, 5, None),
@@ -278,29 +311,68 @@ lazy_static! {
         OpCode::new(0xBE, String::from("CMP M"), Operation::CMP, 1, 7, None),
         OpCode::new(0xBF, String::from("CMP A"), Operation::CMP, 1, 4, None),
 
+        OpCode::new(0xC0, String::from("RNZ"), Operation::RNZ, 1, 5, Some(11)),
         OpCode::new(0xC1, String::from("POP B"), Operation::POP, 1, 10, None),
+        OpCode::new(0xC2, String::from("JNZ"), Operation::JNZ, 3, 10, None),
+        OpCode::new(0xC3, String::from("JMP"), Operation::JMP, 3, 10, None),
+        OpCode::new(0xC4, String::from("CNZ"), Operation::CNZ, 3, 11, Some(17)),
         OpCode::new(0xC5, String::from("PUSH B"), Operation::PUSH, 1, 11, None),
         OpCode::new(0xC6, String::from("ADI"), Operation::ADI, 2, 7, None),
+        OpCode::new(0xC7, String::from("RST 0"), Operation::RST, 1, 11, None),
+        OpCode::new(0xC8, String::from("RZ"), Operation::RZ, 1, 5, Some(11)),
+        OpCode::new(0xC9, String::from("RET"), Operation::RET, 1, 10, None),
+        OpCode::new(0xCA, String::from("JZ"), Operation::JZ, 3, 10, None),
+        OpCode::new(0xCC, String::from("CZ"), Operation::CZ, 3, 11, Some(17)),
+        OpCode::new(0xCD, String::from("CALL"), Operation::CALL, 3, 17, None),
         OpCode::new(0xCE, String::from("ACI"), Operation::ACI, 2, 7, None),
+        OpCode::new(0xCF, String::from("RST 1"), Operation::RST, 1, 11, None),
 
+        OpCode::new(0xD0, String::from("RNC"), Operation::RNC, 1, 5, Some(11)),
         OpCode::new(0xD1, String::from("POP D"), Operation::POP, 1, 10, None),
+        OpCode::new(0xD2, String::from("JNC"), Operation::JNC, 3, 10, None),
+        OpCode::new(0xD3, String::from("OUT"), Operation::OUT, 2, 10, None),
+        OpCode::new(0xD4, String::from("CNC"), Operation::CNC, 3, 11, Some(17)),
         OpCode::new(0xD5, String::from("PUSH D"), Operation::PUSH, 1, 11, None),
         OpCode::new(0xD6, String::from("SUI"), Operation::SUI, 2, 7, None),
+        OpCode::new(0xD7, String::from("RST 2"), Operation::RST, 1, 11, None),
+        OpCode::new(0xD8, String::from("RC"), Operation::RC, 1, 5, Some(11)),
+        OpCode::new(0xDA, String::from("JC"), Operation::JC, 3, 10, None),
+        OpCode::new(0xDB, String::from("IN"), Operation::IN, 2, 10, None),
+        OpCode::new(0xDC, String::from("CC"), Operation::CC, 3, 11, Some(17)),
         OpCode::new(0xDE, String::from("SBI"), Operation::SBI, 2, 7, None),
+        OpCode::new(0xDF, String::from("RST 3"), Operation::RST, 1, 11, None),
 
+        OpCode::new(0xE0, String::from("RPO"), Operation::RPO, 1, 5, Some(11)),
         OpCode::new(0xE1, String::from("POP H"), Operation::POP, 1, 10, None),
+        OpCode::new(0xE2, String::from("JPO"), Operation::JPO, 3, 10, None),
         OpCode::new(0xE3, String::from("XTHL"), Operation::XTHL, 1, 18, None),
+        OpCode::new(0xE4, String::from("CPO"), Operation::CPO, 3, 11, Some(17)),
         OpCode::new(0xE5, String::from("PUSH H"), Operation::PUSH, 1, 11, None),
         OpCode::new(0xE6, String::from("ANI"), Operation::ANI, 2, 7, None),
+        OpCode::new(0xE7, String::from("RST 4"), Operation::RST, 1, 11, None),
+        OpCode::new(0xE8, String::from("RPE"), Operation::RPE, 1, 5, Some(11)),
         OpCode::new(0xE9, String::from("PCHL"), Operation::PCHL, 1, 5, None),
+        OpCode::new(0xEA, String::from("JPE"), Operation::JPE, 3, 10, None),
         OpCode::new(0xEB, String::from("XCHG"), Operation::XCHG, 1, 5, None),
+        OpCode::new(0xEC, String::from("CPE"), Operation::CPE, 3, 11, Some(17)),
         OpCode::new(0xEE, String::from("XRI"), Operation::XRI, 2, 7, None),
+        OpCode::new(0xEF, String::from("RST 5"), Operation::RST, 1, 11, None),
 
+        OpCode::new(0xF0, String::from("RP"), Operation::RP, 1, 5, Some(11)),
         OpCode::new(0xF1, String::from("POP PSW"), Operation::POP, 1, 10, None),
+        OpCode::new(0xF2, String::from("JP"), Operation::JP, 3, 10, None),
+        OpCode::new(0xF3, String::from("DI"), Operation::DI, 1, 4, None),
+        OpCode::new(0xF4, String::from("CP"), Operation::CP, 3, 11, Some(17)),
         OpCode::new(0xF5, String::from("PUSH PSW"), Operation::PUSH, 1, 11, None),
         OpCode::new(0xF6, String::from("ORI"), Operation::ORI, 2, 7, None),
+        OpCode::new(0xF7, String::from("RST 6"), Operation::RST, 1, 11, None),
+        OpCode::new(0xF8, String::from("RM"), Operation::RM, 1, 5, Some(11)),
         OpCode::new(0xF9, String::from("SPHL"), Operation::SPHL, 1, 5, None),
+        OpCode::new(0xFA, String::from("JM"), Operation::JM, 3, 10, None),
+        OpCode::new(0xFB, String::from("EI"), Operation::EI, 1, 4, None),
+        OpCode::new(0xFC, String::from("CM"), Operation::CM, 3, 11, Some(17)),
         OpCode::new(0xFE, String::from("CPI"), Operation::CPI, 2, 7, None),
+        OpCode::new(0xFF, String::from("RST 7"), Operation::RST, 1, 11, None),
 
     ];
 
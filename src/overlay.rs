@@ -0,0 +1,303 @@
+use std::fs;
+use std::path::Path;
+
+use crate::constants::*;
+
+pub type Colour = [Byte; 3];
+
+pub const WHITE: Colour = [0xFF, 0xFF, 0xFF];
+pub const RED: Colour = [0xFF, 0x00, 0x00];
+pub const GREEN: Colour = [0x00, 0xFF, 0x00];
+
+// The monitor is black and white. Cabinets get their colour from strips of
+// cellophane gel stuck over the screen, while later Midway boards looked the
+// colour of each 8x8 cell up in a PROM
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum OverlayMode {
+    None,
+    Classic,
+    Prom,
+}
+
+// A rectangle of the upright screen covered by one colour of gel
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct OverlayBand {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+    pub colour: Colour,
+}
+
+// The Space Invaders cabinet has red gel over the UFO and green gel over the
+// shields, the player and the reserve ships at the bottom left
+pub const CLASSIC_BANDS: [OverlayBand; 3] = [
+    OverlayBand { top: 32, bottom: 63, left: 0, right: 223, colour: RED },
+    OverlayBand { top: 184, bottom: 239, left: 0, right: 223, colour: GREEN },
+    OverlayBand { top: 240, bottom: 255, left: 16, right: 133, colour: GREEN },
+];
+
+// The colour a lit pixel shows up as at each point of the upright screen
+pub struct Overlay {
+    colours: Vec<Colour>,
+}
+
+impl Overlay {
+
+    pub fn none() -> Overlay {
+        Overlay {
+            colours: vec![WHITE; (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize],
+        }
+    }
+
+    // Anything not covered by a band stays white
+    pub fn from_bands(bands: &[OverlayBand]) -> Overlay {
+        let mut overlay = Overlay::none();
+
+        for band in bands {
+            for y in band.top..=band.bottom.min(DISPLAY_HEIGHT as usize - 1) {
+                for x in band.left..=band.right.min(DISPLAY_WIDTH as usize - 1) {
+                    overlay.colours[y * DISPLAY_WIDTH as usize + x] = band.colour;
+                }
+            }
+        }
+
+        overlay
+    }
+
+    // Each byte of video RAM has a PROM entry, shared by the 8 lines of each
+    // 8x8 cell. Only the lower 3 bits are used: bit 0 red, bit 1 blue and
+    // bit 2 green
+    pub fn from_colour_prom(prom: &[Byte]) -> Result<Overlay, String> {
+        let mut overlay = Overlay::none();
+        let bytes_per_line = (DISPLAY_HEIGHT / 8) as usize;
+
+        for y in 0..DISPLAY_HEIGHT as usize {
+            for x in 0..DISPLAY_WIDTH as usize {
+                let column = (DISPLAY_HEIGHT as usize - 1 - y) / 8;
+                let addr = (x / 8) * bytes_per_line + column;
+
+                let entry = *prom.get(addr)
+                    .ok_or(format!("Colour PROM is too small, expected at least {} bytes", addr + 1))?;
+                let channel = |bit: usize| if entry & (1 << bit) != 0 { 0xFF } else { 0x00 };
+
+                overlay.colours[y * DISPLAY_WIDTH as usize + x] = [channel(0), channel(2), channel(1)];
+            }
+        }
+
+        Ok(overlay)
+    }
+
+    // A user supplied overlay is either a binary PPM image, scaled to fit the
+    // screen, or a band definition file
+    pub fn load(path: &Path) -> Result<Overlay, String> {
+        let data = fs::read(path)
+            .map_err(|e| format!("{} could not be read: {}", path.display(), e))?;
+
+        let overlay = if data.starts_with(b"P6") {
+            parse_ppm(&data)
+        } else {
+            String::from_utf8(data)
+                .map_err(|_| String::from("expected a PPM image or a band definition file"))
+                .and_then(|contents| parse_bands(&contents))
+                .map(|bands| Overlay::from_bands(&bands))
+        };
+
+        overlay.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn colour(&self, x: usize, y: usize) -> Colour {
+        self.colours[y * DISPLAY_WIDTH as usize + x]
+    }
+}
+
+fn parse_bands(contents: &str) -> Result<Vec<OverlayBand>, String> {
+    // One band per line, given in pixels of the upright screen as either
+    // "top bottom colour" or "top bottom left right colour". Colours are a
+    // name or RRGGBB in hex. Blank lines and anything after a # are ignored:
+    //
+    //   32 63 red
+    //   240 255 16 133 00FF00
+    let mut bands = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let fields: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+
+        let band = match fields.as_slice() {
+            [top, bottom, colour] => parse_band(top, bottom, "0", "223", colour),
+            [top, bottom, left, right, colour] => parse_band(top, bottom, left, right, colour),
+            _ => Err(String::from("expected top bottom [left right] colour")),
+        };
+
+        bands.push(band.map_err(|e| format!("line {}: {}", number + 1, e))?);
+    }
+
+    Ok(bands)
+}
+
+fn parse_band(top: &str, bottom: &str, left: &str, right: &str, colour: &str) -> Result<OverlayBand, String> {
+    let position = |value: &str| value.parse::<usize>()
+        .map_err(|_| format!("'{}' is not a position", value));
+
+    let band = OverlayBand {
+        top: position(top)?,
+        bottom: position(bottom)?,
+        left: position(left)?,
+        right: position(right)?,
+        colour: parse_colour(colour)?,
+    };
+
+    if band.top > band.bottom || band.left > band.right {
+        return Err(String::from("band is empty"));
+    }
+
+    Ok(band)
+}
+
+fn parse_colour(value: &str) -> Result<Colour, String> {
+    let colour = match value.to_lowercase().as_str() {
+        "white" => WHITE,
+        "red" => RED,
+        "green" => GREEN,
+        "blue" => [0x00, 0x00, 0xFF],
+        "yellow" => [0xFF, 0xFF, 0x00],
+        "cyan" => [0x00, 0xFF, 0xFF],
+        "magenta" => [0xFF, 0x00, 0xFF],
+        hex if hex.len() == 6 => {
+            let rgb = u32::from_str_radix(hex, 16)
+                .map_err(|_| format!("'{}' is not a colour", value))?;
+            [(rgb >> 16) as Byte, (rgb >> 8) as Byte, rgb as Byte]
+        },
+        _ => return Err(format!("'{}' is not a colour", value)),
+    };
+
+    Ok(colour)
+}
+
+fn parse_ppm(data: &[u8]) -> Result<Overlay, String> {
+    // The header is "P6 width height maxval", separated by whitespace and
+    // possibly comments, followed by a single whitespace byte and the pixels
+    let mut fields = Vec::new();
+    let mut offset = 0;
+
+    while fields.len() < 4 {
+        while offset < data.len() && data[offset].is_ascii_whitespace() {
+            offset += 1;
+        }
+
+        if offset < data.len() && data[offset] == b'#' {
+            while offset < data.len() && data[offset] != b'\n' {
+                offset += 1;
+            }
+            continue;
+        }
+
+        let start = offset;
+        while offset < data.len() && !data[offset].is_ascii_whitespace() {
+            offset += 1;
+        }
+
+        if start == offset {
+            return Err(String::from("truncated PPM header"));
+        }
+
+        fields.push(String::from_utf8_lossy(&data[start..offset]).into_owned());
+    }
+
+    let number = |field: &str| field.parse::<usize>()
+        .map_err(|_| format!("invalid PPM header field '{}'", field));
+
+    let (width, height, max_value) = (number(&fields[1])?, number(&fields[2])?, number(&fields[3])?);
+    if width == 0 || height == 0 || max_value == 0 || max_value > 255 {
+        return Err(String::from("unsupported PPM format"));
+    }
+
+    let pixels = &data[(offset + 1).min(data.len())..];
+    if pixels.len() < width * height * 3 {
+        return Err(String::from("truncated PPM image"));
+    }
+
+    // Nearest neighbour scaling to the screen size
+    let mut overlay = Overlay::none();
+
+    for y in 0..DISPLAY_HEIGHT as usize {
+        for x in 0..DISPLAY_WIDTH as usize {
+            let source_x = x * width / DISPLAY_WIDTH as usize;
+            let source_y = y * height / DISPLAY_HEIGHT as usize;
+            let pixel = (source_y * width + source_x) * 3;
+
+            let scale = |value: u8| (value as usize * 255 / max_value) as Byte;
+            overlay.colours[y * DISPLAY_WIDTH as usize + x] =
+                [scale(pixels[pixel]), scale(pixels[pixel + 1]), scale(pixels[pixel + 2])];
+        }
+    }
+
+    Ok(overlay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_bands() {
+        let overlay = Overlay::from_bands(&CLASSIC_BANDS);
+
+        assert_eq!(overlay.colour(100, 10), WHITE);
+        assert_eq!(overlay.colour(100, 40), RED);
+        assert_eq!(overlay.colour(100, 200), GREEN);
+        assert_eq!(overlay.colour(20, 250), GREEN);
+        assert_eq!(overlay.colour(200, 250), WHITE);
+    }
+
+    #[test]
+    fn test_colour_prom() {
+        // Red for the first cell, then green for the cell above it
+        let mut prom = vec![0; 0x400];
+        prom[0] = 0b001;
+        prom[1] = 0b100;
+
+        let overlay = Overlay::from_colour_prom(&prom).unwrap();
+        assert_eq!(overlay.colour(0, 255), RED);
+        assert_eq!(overlay.colour(7, 248), RED);
+        assert_eq!(overlay.colour(0, 247), GREEN);
+        assert_eq!(overlay.colour(8, 255), [0, 0, 0]);
+
+        assert!(Overlay::from_colour_prom(&prom[..0x100]).is_err());
+    }
+
+    #[test]
+    fn test_parse_bands() {
+        let bands = parse_bands("
+            # UFO
+            32 63 red
+            240 255 16 133 00ff80   # Reserve ships
+        ").unwrap();
+
+        assert_eq!(bands, [
+            OverlayBand { top: 32, bottom: 63, left: 0, right: 223, colour: RED },
+            OverlayBand { top: 240, bottom: 255, left: 16, right: 133, colour: [0x00, 0xFF, 0x80] },
+        ]);
+
+        assert_eq!(parse_bands("32 63").unwrap_err(), "line 1: expected top bottom [left right] colour");
+        assert!(parse_bands("32 63 purple").is_err());
+        assert!(parse_bands("63 32 red").is_err());
+    }
+
+    #[test]
+    fn test_parse_ppm() {
+        // A 2x2 image, red across the top half and blue across the bottom
+        let mut ppm = b"P6\n# overlay\n2 2\n255\n".to_vec();
+        ppm.extend_from_slice(&[0xFF, 0, 0, 0xFF, 0, 0, 0, 0, 0xFF, 0, 0, 0xFF]);
+
+        let overlay = parse_ppm(&ppm).unwrap();
+        assert_eq!(overlay.colour(0, 0), RED);
+        assert_eq!(overlay.colour(223, 127), RED);
+        assert_eq!(overlay.colour(0, 128), [0, 0, 0xFF]);
+
+        assert!(parse_ppm(&ppm[..20]).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::constants::*;
+
+// A single ROM chip of a set, along with where it is loaded in memory
+// and the size and CRC32 of a known-good dump
+#[derive(Debug, Copy, Clone)]
+pub struct RomChip {
+    pub name: &'static str,
+    pub addr: Word,
+    pub size: usize,
+    pub crc: u32,
+}
+
+#[derive(Debug)]
+pub enum RomError {
+    Missing { file: PathBuf, source: io::Error },
+    NotInArchive { archive: PathBuf, name: &'static str },
+    BadArchive { archive: PathBuf, source: ZipError },
+    // The file is where the chip was read from: its own file, or the archive
+    // or image holding it. A whole image of the wrong size has no chip
+    WrongSize { file: PathBuf, chip: Option<&'static str>, expected: usize, actual: usize },
+    BadChecksum { file: PathBuf, chip: &'static str, expected: u32, actual: u32 },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Missing { file, source } =>
+                write!(f, "ROM file {} could not be read: {}", file.display(), source),
+            RomError::NotInArchive { archive, name } =>
+                write!(f, "ROM {} was not found in {} by name or CRC", name, archive.display()),
+            RomError::BadArchive { archive, source } =>
+                write!(f, "ROM archive {} could not be read: {}", archive.display(), source),
+            RomError::WrongSize { file, chip: Some(chip), expected, actual } =>
+                write!(f, "ROM {} in {} is {} bytes, expected {}", chip, file.display(), actual, expected),
+            RomError::WrongSize { file, chip: None, expected, actual } =>
+                write!(f, "ROM file {} is {} bytes, expected {}", file.display(), actual, expected),
+            RomError::BadChecksum { file, chip, expected, actual } =>
+                write!(f, "ROM {} in {} has CRC32 {:08X}, expected {:08X} (bad or wrong dump)", chip, file.display(), actual, expected),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::Missing { source, .. } => Some(source),
+            RomError::BadArchive { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+pub struct RomSet {
+    pub chips: Vec<(RomChip, Vec<Byte>)>,
+}
+
+impl RomSet {
+
+    pub fn load(path: &Path, manifest: &[RomChip]) -> Result<RomSet, RomError> {
+        // ROM sets are distributed in a few different ways:
+        //   - A directory holding each chip as a separate file
+        //   - A MAME style .zip archive of those same files
+        //   - A single image of every chip concatenated in address order
+        if path.is_dir() {
+            load_directory(path, manifest)
+        } else if is_zip(path) {
+            load_zip(path, manifest)
+        } else {
+            load_image(path, manifest)
+        }
+    }
+
+    // Loads the program ROMs along with the colour PROMs, which aren't part
+    // of the address space. A single image holds the PROMs straight after
+    // the program ROMs
+    pub fn load_with_proms(path: &Path, roms: &[RomChip], proms: &[RomChip]) -> Result<(RomSet, RomSet), RomError> {
+        if path.is_dir() || is_zip(path) {
+            return Ok((RomSet::load(path, roms)?, RomSet::load(path, proms)?));
+        }
+
+        let manifest: Vec<RomChip> = roms.iter().chain(proms).copied().collect();
+        let mut rom_set = load_image(path, &manifest)?;
+        let prom_set = RomSet { chips: rom_set.chips.split_off(roms.len()) };
+
+        Ok((rom_set, prom_set))
+    }
+
+    // The inclusive address ranges the set covers, merging chips that follow
+    // on from each other
+    pub fn ranges(&self) -> Vec<(Word, Word)> {
+        let mut chips: Vec<&RomChip> = self.chips.iter().map(|(chip, _)| chip).collect();
+        chips.sort_by_key(|chip| chip.addr);
+
+        let mut ranges: Vec<(Word, Word)> = Vec::new();
+
+        for chip in chips {
+            let end = chip.addr + (chip.size as Word - 1);
+
+            match ranges.last_mut() {
+                Some(range) if range.1.wrapping_add(1) == chip.addr => range.1 = end,
+                _ => ranges.push((chip.addr, end)),
+            };
+        }
+
+        ranges
+    }
+
+    // Lays every chip out at its address, for sets like colour PROMs that are
+    // not loaded into the CPU's address space
+    pub fn data(&self) -> Vec<Byte> {
+        let len = self.chips.iter().map(|(chip, data)| chip.addr as usize + data.len()).max().unwrap_or(0);
+        let mut data = vec![0; len];
+
+        for (chip, chip_data) in &self.chips {
+            let start = chip.addr as usize;
+            data[start..start + chip_data.len()].copy_from_slice(chip_data);
+        }
+
+        data
+    }
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+fn load_directory(dir: &Path, manifest: &[RomChip]) -> Result<RomSet, RomError> {
+    let mut chips = Vec::new();
+
+    for chip in manifest {
+        let file = dir.join(chip.name);
+        let data = fs::read(&file)
+            .map_err(|source| RomError::Missing { file: file.clone(), source })?;
+
+        verify_chip(chip, &data, &file)?;
+        chips.push((*chip, data));
+    }
+
+    Ok(RomSet { chips })
+}
+
+fn load_zip(path: &Path, manifest: &[RomChip]) -> Result<RomSet, RomError> {
+    let bad_archive = |source| RomError::BadArchive { archive: path.to_path_buf(), source };
+
+    let file = File::open(path)
+        .map_err(|source| RomError::Missing { file: path.to_path_buf(), source })?;
+    let mut archive = ZipArchive::new(file).map_err(bad_archive)?;
+
+    // Dumps are often renamed between releases, so fall back to matching
+    // on the CRC stored in the archive if the name isn't found
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(bad_archive)?;
+        let name = entry.name().rsplit('/').next().unwrap_or("").to_lowercase();
+        entries.push((name, entry.crc32()));
+    }
+
+    let mut chips = Vec::new();
+
+    for chip in manifest {
+        let index = entries.iter().position(|(name, _)| name == chip.name)
+            .or_else(|| entries.iter().position(|(_, crc)| *crc == chip.crc))
+            .ok_or(RomError::NotInArchive { archive: path.to_path_buf(), name: chip.name })?;
+
+        let mut data = Vec::new();
+        archive.by_index(index).map_err(bad_archive)?
+            .read_to_end(&mut data)
+            .map_err(|source| RomError::Missing { file: path.to_path_buf(), source })?;
+
+        verify_chip(chip, &data, path)?;
+        chips.push((*chip, data));
+    }
+
+    Ok(RomSet { chips })
+}
+
+fn load_image(path: &Path, manifest: &[RomChip]) -> Result<RomSet, RomError> {
+    let data = fs::read(path)
+        .map_err(|source| RomError::Missing { file: path.to_path_buf(), source })?;
+
+    let expected = manifest.iter().map(|chip| chip.size).sum();
+    if data.len() != expected {
+        return Err(RomError::WrongSize { file: path.to_path_buf(), chip: None, expected, actual: data.len() });
+    }
+
+    let mut chips = Vec::new();
+    let mut offset = 0;
+
+    for chip in manifest {
+        let chip_data = data[offset..offset + chip.size].to_vec();
+        verify_chip(chip, &chip_data, path)?;
+
+        chips.push((*chip, chip_data));
+        offset += chip.size;
+    }
+
+    Ok(RomSet { chips })
+}
+
+fn verify_chip(chip: &RomChip, data: &[Byte], file: &Path) -> Result<(), RomError> {
+    if data.len() != chip.size {
+        return Err(RomError::WrongSize { file: file.to_path_buf(), chip: Some(chip.name), expected: chip.size, actual: data.len() });
+    }
+
+    let crc = crc32fast::hash(data);
+    if crc != chip.crc {
+        return Err(RomError::BadChecksum { file: file.to_path_buf(), chip: chip.name, expected: chip.crc, actual: crc });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch directory for a test's ROM files, removed again when the
+    // test finishes
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> TestDir {
+            let dir = std::env::temp_dir().join(format!("space-invaders-rom-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+    }
+
+    impl std::ops::Deref for TestDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn test_manifest() -> [RomChip; 2] {
+        [
+            RomChip { name: "test.a", addr: 0x0000, size: 4, crc: crc32fast::hash(&[1, 2, 3, 4]) },
+            RomChip { name: "test.b", addr: 0x0004, size: 4, crc: crc32fast::hash(&[5, 6, 7, 8]) },
+        ]
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TestDir::new("load");
+        fs::write(dir.join("test.a"), [1, 2, 3, 4]).unwrap();
+        fs::write(dir.join("test.b"), [5, 6, 7, 8]).unwrap();
+
+        let rom_set = RomSet::load(&dir, &test_manifest()).unwrap();
+
+        assert_eq!(rom_set.chips.len(), 2);
+        assert_eq!(rom_set.chips[1].0.addr, 0x0004);
+        assert_eq!(rom_set.chips[1].1, vec![5, 6, 7, 8]);
+        assert_eq!(rom_set.data(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(rom_set.ranges(), vec![(0x0000, 0x0007)]);
+    }
+
+    #[test]
+    fn test_load_zip() {
+        use std::io::Write;
+        use zip::write::{FileOptions, ZipWriter};
+
+        // test.a is stored under its own name, test.b has been renamed
+        // and can only be found by its CRC
+        let dir = TestDir::new("zip");
+        let path = dir.join("test.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer.start_file("set/TEST.A", FileOptions::default()).unwrap();
+        writer.write_all(&[1, 2, 3, 4]).unwrap();
+        writer.start_file("renamed.bin", FileOptions::default()).unwrap();
+        writer.write_all(&[5, 6, 7, 8]).unwrap();
+        writer.finish().unwrap();
+
+        let rom_set = RomSet::load(&path, &test_manifest()).unwrap();
+
+        assert_eq!(rom_set.chips[0].1, vec![1, 2, 3, 4]);
+        assert_eq!(rom_set.chips[1].1, vec![5, 6, 7, 8]);
+
+        let mut manifest = test_manifest();
+        manifest[1].crc = 0;
+        match RomSet::load(&path, &manifest) {
+            Err(RomError::NotInArchive { name, .. }) => assert_eq!(name, "test.b"),
+            _ => panic!("Expected test.b to be missing from the archive"),
+        }
+    }
+
+    #[test]
+    fn test_load_image() {
+        let dir = TestDir::new("image");
+        let path = dir.join("test.rom");
+
+        fs::write(&path, [1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let rom_set = RomSet::load(&path, &test_manifest()).unwrap();
+
+        assert_eq!(rom_set.chips[0].1, vec![1, 2, 3, 4]);
+        assert_eq!(rom_set.chips[1].1, vec![5, 6, 7, 8]);
+
+        fs::write(&path, [1, 2, 3, 4, 5, 6, 7]).unwrap();
+        match RomSet::load(&path, &test_manifest()) {
+            Err(RomError::WrongSize { expected: 8, actual: 7, .. }) => (),
+            _ => panic!("Expected the image to be the wrong size"),
+        }
+
+        fs::write(&path, [1, 2, 3, 4, 5, 6, 7, 9]).unwrap();
+        match RomSet::load(&path, &test_manifest()) {
+            Err(e @ RomError::BadChecksum { .. }) => {
+                assert_eq!(e.to_string(), format!("ROM test.b in {} has CRC32 {:08X}, expected {:08X} (bad or wrong dump)",
+                    path.display(), crc32fast::hash(&[5, 6, 7, 9]), crc32fast::hash(&[5, 6, 7, 8])));
+            },
+            _ => panic!("Expected test.b to fail its checksum"),
+        }
+    }
+
+    #[test]
+    fn test_load_with_proms() {
+        let proms = [RomChip { name: "test.prom", addr: 0x0000, size: 2, crc: crc32fast::hash(&[9, 10]) }];
+
+        let dir = TestDir::new("proms");
+        fs::write(dir.join("test.a"), [1, 2, 3, 4]).unwrap();
+        fs::write(dir.join("test.b"), [5, 6, 7, 8]).unwrap();
+        fs::write(dir.join("test.prom"), [9, 10]).unwrap();
+
+        let (rom_set, prom_set) = RomSet::load_with_proms(&dir, &test_manifest(), &proms).unwrap();
+        assert_eq!(rom_set.data(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(prom_set.data(), vec![9, 10]);
+
+        // An image carries the PROMs after the program ROMs
+        let path = dir.join("test.rom");
+        fs::write(&path, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]).unwrap();
+
+        let (rom_set, prom_set) = RomSet::load_with_proms(&path, &test_manifest(), &proms).unwrap();
+        assert_eq!(rom_set.chips.len(), 2);
+        assert_eq!(rom_set.data(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(prom_set.data(), vec![9, 10]);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = TestDir::new("errors");
+        fs::write(dir.join("test.a"), [1, 2, 3, 4]).unwrap();
+
+        match RomSet::load(&dir, &test_manifest()) {
+            Err(RomError::Missing { file, .. }) => assert!(file.ends_with("test.b")),
+            _ => panic!("Expected test.b to be missing"),
+        }
+
+        fs::write(dir.join("test.b"), [5, 6, 7]).unwrap();
+        match RomSet::load(&dir, &test_manifest()) {
+            Err(RomError::WrongSize { expected: 4, actual: 3, .. }) => (),
+            _ => panic!("Expected test.b to be the wrong size"),
+        }
+
+        fs::write(dir.join("test.b"), [5, 6, 7, 9]).unwrap();
+        match RomSet::load(&dir, &test_manifest()) {
+            Err(RomError::BadChecksum { file, chip, .. }) => assert_eq!((file, chip), (dir.join("test.b"), "test.b")),
+            _ => panic!("Expected test.b to fail its checksum"),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::constants::*;
+
+// The MB14241 is an external barrel shifter the game uses to draw sprites
+// at arbitrary pixel offsets. It holds the last two bytes written to it as
+// a 16-bit value, and reading returns 8 bits of that value starting at a
+// chosen offset from the top:
+//
+//   OUT 4  shifts a new byte into the high end, moving the old high byte down
+//   OUT 2  sets the offset (only the lower 3 bits are used)
+//   IN 3   reads (value << offset) >> 8
+#[derive(Default)]
+pub struct ShiftRegister {
+    value: Word,
+    offset: Byte,
+}
+
+impl ShiftRegister {
+
+    pub fn new() -> ShiftRegister {
+        ShiftRegister {
+            value: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn write_offset(&mut self, data: Byte) {
+        self.offset = data & 0x07;
+    }
+
+    pub fn write_data(&mut self, data: Byte) {
+        self.value = ((data as Word) << 8) | (self.value >> 8);
+    }
+
+    pub fn read_result(&self) -> Byte {
+        (self.value >> (8 - self.offset)) as Byte
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_data() {
+        let mut shift_register = ShiftRegister::new();
+
+        shift_register.write_data(0xAB);
+        assert_eq!(shift_register.value, 0xAB00);
+
+        shift_register.write_data(0xCD);
+        assert_eq!(shift_register.value, 0xCDAB);
+
+        shift_register.write_data(0xEF);
+        assert_eq!(shift_register.value, 0xEFCD);
+    }
+
+    #[test]
+    fn test_write_offset() {
+        let mut shift_register = ShiftRegister::new();
+
+        shift_register.write_offset(0x05);
+        assert_eq!(shift_register.offset, 0x05);
+
+        // Only the lower 3 bits are wired up
+        shift_register.write_offset(0xFB);
+        assert_eq!(shift_register.offset, 0x03);
+    }
+
+    #[test]
+    fn test_read_result() {
+        let mut shift_register = ShiftRegister::new();
+
+        shift_register.write_data(0xAB);
+        shift_register.write_data(0xCD);
+
+        // An offset of 0 returns the most recently written byte
+        shift_register.write_offset(0);
+        assert_eq!(shift_register.read_result(), 0xCD);
+
+        shift_register.write_offset(3);
+        assert_eq!(shift_register.read_result(), 0x6D);
+
+        shift_register.write_offset(7);
+        assert_eq!(shift_register.read_result(), 0xD5);
+    }
+
+    #[test]
+    fn test_sprite_sequence() {
+        // The ROM draws a sprite row by clearing the register with a 0,
+        // then shifting in each byte of the sprite and reading the result
+        // back at the sprite's pixel offset
+        let mut shift_register = ShiftRegister::new();
+        shift_register.write_offset(2);
+
+        shift_register.write_data(0x00);
+        shift_register.write_data(0xFF);
+        assert_eq!(shift_register.read_result(), 0xFC);
+
+        shift_register.write_data(0x00);
+        assert_eq!(shift_register.read_result(), 0x03);
+
+        // Changing the offset doesn't disturb the data
+        shift_register.write_offset(0);
+        assert_eq!(shift_register.read_result(), 0x00);
+        shift_register.write_offset(4);
+        assert_eq!(shift_register.read_result(), 0x0F);
+    }
+}
@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use crate::constants::*;
+use crate::synth::*;
+use crate::utils::*;
+use crate::wav::*;
+
+// Where the sound effects come from
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum SoundMode {
+    Samples,
+    Synth,
+    Off,
+}
+
+// The sounds the board can make, numbered as in the standard sample set
+pub const SOUND_COUNT: usize = 10;
+pub const UFO_SOUND: usize = 0;
+pub const SHOT_SOUND: usize = 1;
+pub const PLAYER_DIE_SOUND: usize = 2;
+pub const INVADER_DIE_SOUND: usize = 3;
+pub const FLEET_SOUNDS: [usize; 4] = [4, 5, 6, 7];
+pub const UFO_HIT_SOUND: usize = 8;
+pub const EXTRA_SHIP_SOUND: usize = 9;
+
+// How a game triggers each sound through the bits of its two sound latches.
+// A sound starts when its bit goes from 0 to 1, and the looping sound keeps
+// playing for as long as its bit stays set
+#[derive(Debug)]
+pub struct SoundLayout {
+    pub bits: [[Option<usize>; 8]; 2],
+    pub looping: Option<usize>,
+}
+
+// A sound that is currently playing, and how far through it we are
+struct Voice {
+    sound: usize,
+    position: usize,
+    looping: bool,
+}
+
+pub struct Sound {
+    layout: &'static SoundLayout,
+    samples: Vec<Vec<i16>>,
+    voices: Vec<Voice>,
+    port_values: [Byte; 2],
+}
+
+impl Sound {
+
+    pub fn new(layout: &'static SoundLayout, samples: Vec<Vec<i16>>) -> Sound {
+        Sound {
+            layout: layout,
+            samples: samples,
+            voices: Vec::new(),
+            port_values: [0; 2],
+        }
+    }
+
+    pub fn silent(layout: &'static SoundLayout) -> Sound {
+        Sound::new(layout, vec![Vec::new(); SOUND_COUNT])
+    }
+
+    pub fn synthesized(layout: &'static SoundLayout) -> Sound {
+        Sound::new(layout, synthesize())
+    }
+
+    // Loads 0.wav - 9.wav from a samples directory. The error lists every
+    // file that couldn't be loaded, one per line
+    pub fn load_samples(layout: &'static SoundLayout, dir: &Path) -> Result<Sound, String> {
+        let mut samples = Vec::new();
+        let mut errors = Vec::new();
+
+        for sound in 0..SOUND_COUNT {
+            match read_wav(&dir.join(format!("{}.wav", sound))) {
+                Ok(sample) => samples.push(sample),
+                Err(e) => errors.push(e),
+            };
+        }
+
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+
+        Ok(Sound::new(layout, samples))
+    }
+
+    pub fn write_port(&mut self, port: usize, data: Byte) {
+        let previous = self.port_values[port];
+        self.port_values[port] = data;
+
+        for (bit, sound) in self.layout.bits[port].iter().enumerate() {
+            let sound = match sound {
+                Some(sound) => *sound,
+                None => continue,
+            };
+
+            match (is_bit_set(&previous, bit), is_bit_set(&data, bit)) {
+                (false, true) => self.play(sound),
+                (true, false) if self.layout.looping == Some(sound) => self.stop(sound),
+                _ => (),
+            };
+        }
+    }
+
+    // Clears the sound latches, silencing everything
+    pub fn reset(&mut self) {
+        self.port_values = [0; 2];
+        self.voices.clear();
+    }
+
+    // Mixes the playing sounds into the buffer, replacing its contents
+    pub fn render(&mut self, buffer: &mut [i16]) {
+        buffer.fill(0);
+
+        for voice in self.voices.iter_mut() {
+            let sample = &self.samples[voice.sound];
+
+            for out in buffer.iter_mut() {
+                if voice.position >= sample.len() {
+                    if !voice.looping || sample.is_empty() {
+                        break;
+                    }
+
+                    voice.position = 0;
+                }
+
+                *out = out.saturating_add(sample[voice.position]);
+                voice.position += 1;
+            }
+        }
+
+        let samples = &self.samples;
+        self.voices.retain(|voice| voice.looping || voice.position < samples[voice.sound].len());
+    }
+
+    fn play(&mut self, sound: usize) {
+        // Retriggering a sound starts it again from the beginning
+        self.stop(sound);
+        self.voices.push(Voice { sound: sound, position: 0, looping: self.layout.looping == Some(sound) });
+    }
+
+    fn stop(&mut self, sound: usize) {
+        self.voices.retain(|voice| voice.sound != sound);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::invaders::*;
+
+    fn test_sound() -> Sound {
+        // Each sound is 3 samples of its own number + 1
+        Sound::new(&INVADERS_SOUNDS, (0..SOUND_COUNT).map(|sound| vec![sound as i16 + 1; 3]).collect())
+    }
+
+    #[test]
+    fn test_rising_edges() {
+        let mut sound = test_sound();
+        let mut buffer = [0; 4];
+
+        // Shot and invader death
+        sound.write_port(0, 0b0000_1010);
+        sound.render(&mut buffer);
+        assert_eq!(buffer, [6, 6, 6, 0]);
+
+        // Holding the bits doesn't retrigger them
+        sound.write_port(0, 0b0000_1010);
+        sound.render(&mut buffer);
+        assert_eq!(buffer, [0, 0, 0, 0]);
+
+        // Fleet note 2 and UFO hit, with shot going back to 0
+        sound.write_port(0, 0b0000_1000);
+        sound.write_port(1, 0b0001_0010);
+        sound.render(&mut buffer);
+        assert_eq!(buffer, [15, 15, 15, 0]);
+    }
+
+    #[test]
+    fn test_missing_samples() {
+        let dir = Path::new("no-such-samples");
+
+        match Sound::load_samples(&INVADERS_SOUNDS, dir) {
+            Err(e) => {
+                assert_eq!(e.lines().count(), SOUND_COUNT);
+                assert!(e.contains(&dir.join("0.wav").display().to_string()));
+                assert!(e.contains(&dir.join("9.wav").display().to_string()));
+            },
+            Ok(_) => panic!("Expected the samples to be missing"),
+        };
+    }
+
+    #[test]
+    fn test_ufo_loops() {
+        let mut sound = test_sound();
+        let mut buffer = [0; 4];
+
+        sound.write_port(0, 0b0000_0001);
+        sound.render(&mut buffer);
+        assert_eq!(buffer, [1, 1, 1, 1]);
+        sound.render(&mut buffer);
+        assert_eq!(buffer, [1, 1, 1, 1]);
+
+        sound.write_port(0, 0b0000_0000);
+        sound.render(&mut buffer);
+        assert_eq!(buffer, [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::f32::consts::PI;
+
+use crate::constants::*;
+use crate::sound::*;
+
+// Loudest a single synthesized sound gets, leaving headroom for several
+// of them to play at once
+const PEAK: f32 = 8000.0;
+
+// Builds every sound the board makes, approximating its discrete analog
+// circuits rather than playing back recordings of them
+pub fn synthesize() -> Vec<Vec<i16>> {
+    let mut sounds = vec![Vec::new(); SOUND_COUNT];
+
+    sounds[UFO_SOUND] = ufo();
+    sounds[SHOT_SOUND] = shot();
+    sounds[PLAYER_DIE_SOUND] = explosion(1.0, 0.05);
+    sounds[INVADER_DIE_SOUND] = explosion(0.3, 0.3);
+    sounds[UFO_HIT_SOUND] = ufo_hit();
+    sounds[EXTRA_SHIP_SOUND] = extra_ship();
+
+    // The fleet steps through four low notes as it marches
+    for (sound, frequency) in FLEET_SOUNDS.iter().zip([62.0, 58.0, 55.0, 52.0]) {
+        sounds[*sound] = fleet_note(frequency);
+    }
+
+    sounds
+}
+
+// A siren swept up and down by a triangle wave. The buffer holds exactly
+// one sweep so that it can loop for as long as the UFO is on screen
+fn ufo() -> Vec<i16> {
+    let sweep = 0.16;
+
+    tone(sweep, square, |t| {
+        let phase = t / sweep;
+        let triangle = 1.0 - (2.0 * phase - 1.0).abs();
+        600.0 + 500.0 * triangle
+    }, |_| 0.4)
+}
+
+// A tone that falls quickly in pitch as the shot leaves the cannon
+fn shot() -> Vec<i16> {
+    let duration = 0.25;
+
+    tone(duration, square, |t| 1800.0 * (1.0 - t / duration) + 300.0, |t| 0.6 * decay(t, duration))
+}
+
+// White noise, low passed by cutoff (0 - 1) and dying away over the duration
+fn explosion(duration: f32, cutoff: f32) -> Vec<i16> {
+    let len = (duration * SAMPLE_RATE as f32).round() as usize;
+
+    // The noise comes from a 17-bit linear feedback shift register
+    let mut lfsr: u32 = 0x1FFFF;
+    let mut filtered = 0.0;
+
+    (0..len).map(|i| {
+        let bit = (lfsr ^ (lfsr >> 3)) & 1;
+        lfsr = (lfsr >> 1) | (bit << 16);
+
+        let noise = if lfsr & 1 == 1 { 1.0 } else { -1.0 };
+        filtered += cutoff * (noise - filtered);
+
+        let t = i as f32 / SAMPLE_RATE as f32;
+        to_sample(filtered * decay(t, duration))
+    }).collect()
+}
+
+fn fleet_note(frequency: f32) -> Vec<i16> {
+    let duration = 0.12;
+
+    tone(duration, square, |_| frequency, |t| decay(t, duration))
+}
+
+// The siren again, sliding down as it fades out
+fn ufo_hit() -> Vec<i16> {
+    let duration = 1.0;
+
+    tone(duration, square, |t| {
+        let warble = (t * 2.0 * PI * 12.0).sin();
+        900.0 - 500.0 * t / duration + 150.0 * warble
+    }, |t| 0.5 * decay(t, duration))
+}
+
+// A bright tone beeping on and off
+fn extra_ship() -> Vec<i16> {
+    let duration = 1.0;
+
+    tone(duration, sine, |_| 1000.0, |t| if (t * 8.0).fract() < 0.5 { 0.6 } else { 0.0 })
+}
+
+// Generates a wave whose frequency (in Hz) and amplitude (0 - 1) can both
+// change over time. The phase is accumulated so that sweeps stay smooth
+fn tone(duration: f32, wave: fn(f32) -> f32, frequency: impl Fn(f32) -> f32, amplitude: impl Fn(f32) -> f32) -> Vec<i16> {
+    let len = (duration * SAMPLE_RATE as f32).round() as usize;
+    let mut phase = 0.0;
+
+    (0..len).map(|i| {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        phase = (phase + frequency(t) / SAMPLE_RATE as f32).fract();
+
+        to_sample(wave(phase) * amplitude(t))
+    }).collect()
+}
+
+fn square(phase: f32) -> f32 {
+    if phase < 0.5 { 1.0 } else { -1.0 }
+}
+
+fn sine(phase: f32) -> f32 {
+    (phase * 2.0 * PI).sin()
+}
+
+// Linear fade from full volume at the start to silence at the end
+fn decay(t: f32, duration: f32) -> f32 {
+    (1.0 - t / duration).max(0.0)
+}
+
+fn to_sample(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * PEAK) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthesize() {
+        let sounds = synthesize();
+        assert_eq!(sounds.len(), SOUND_COUNT);
+
+        for sound in &sounds {
+            assert!(!sound.is_empty());
+            assert!(sound.iter().all(|s| s.unsigned_abs() <= PEAK as u16));
+        }
+
+        // Synthesis is deterministic, so recordings can be compared
+        assert_eq!(sounds, synthesize());
+    }
+
+    #[test]
+    fn test_tone() {
+        // 0.01 seconds of 100 Hz is a single cycle, high for the first half
+        let samples = tone(0.01, square, |_| 100.0, |_| 1.0);
+        assert_eq!(samples.len(), SAMPLE_RATE / 100);
+        assert!(samples[..200].iter().all(|&s| s == PEAK as i16));
+        assert!(samples[240..430].iter().all(|&s| s == -PEAK as i16));
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::constants::*;
+use crate::cpu::*;
+use crate::disassembler::*;
+
+// Which instructions make it into the trace. Instructions are counted from
+// the first one executed, whether or not they are in the PC range
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TraceFilter {
+    pub pc_range: Option<(Word, Word)>,
+    pub skip: u64,
+    pub limit: Option<u64>,
+}
+
+// Logs the CPU state before each instruction executes, one line per
+// instruction with fixed width columns so that traces can be diffed:
+//
+//   PC    Bytes     Instruction   Registers and SP                                     Cycles so far
+//   0000  00        NOP           A:00 F:01 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 CYC:0
+//   0001  C3 D4 18  JMP 18D4H     A:00 F:01 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 CYC:4
+//
+// Interrupts are not instructions fetched from memory, so they don't get a
+// line of their own. The RST shows up as a jump to its vector
+pub struct Tracer {
+    output: Box<dyn Write>,
+    filter: TraceFilter,
+    executed: u64,
+    written: u64,
+    failed: bool,
+}
+
+impl Tracer {
+
+    pub fn new(output: Box<dyn Write>, filter: TraceFilter) -> Tracer {
+        Tracer {
+            output: output,
+            filter: filter,
+            executed: 0,
+            written: 0,
+            failed: false,
+        }
+    }
+
+    pub fn create(path: &Path, filter: TraceFilter) -> Result<Tracer, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("{} could not be created: {}", path.display(), e))?;
+
+        Ok(Tracer::new(Box::new(BufWriter::new(file)), filter))
+    }
+
+    pub fn trace(&mut self, cpu: &Cpu, cycles: u64) {
+        self.executed += 1;
+
+        if self.failed || self.executed <= self.filter.skip || self.filter.limit.is_some_and(|limit| self.written >= limit) {
+            return;
+        }
+
+        let pc = cpu.program_counter();
+        if self.filter.pc_range.is_some_and(|(start, end)| pc < start || pc > end) {
+            return;
+        }
+
+        self.written += 1;
+
+        if let Err(e) = writeln!(self.output, "{}", format_trace_line(cpu, cycles)) {
+            eprintln!("Trace could not be written, tracing stopped: {}", e);
+            self.failed = true;
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.output.flush().map_err(|e| format!("Trace could not be written: {}", e))
+    }
+}
+
+pub fn format_trace_line(cpu: &Cpu, cycles: u64) -> String {
+    let instruction = disassemble(cpu.bus(), cpu.program_counter());
+    let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    let register = |register| cpu.get_register(register);
+
+    format!(
+        "{:04X}  {:<8}  {:<12}  A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} CYC:{}",
+        instruction.addr, bytes.join(" "), instruction.text(),
+        register(Register::A), register(Register::F), register(Register::B), register(Register::C),
+        register(Register::D), register(Register::E), register(Register::H), register(Register::L),
+        register(Register::SP), cycles,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::bus::*;
+
+    // Collects the trace so the test can look at it after handing it over
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace_lines(output: &SharedOutput) -> Vec<String> {
+        String::from_utf8(output.0.borrow().clone()).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_format_trace_line() {
+        let mut cpu = Cpu::new(Bus::new());
+        cpu.reset();
+        cpu.bus_mut().write_byte(0x0000, 0x06);
+        cpu.bus_mut().write_byte(0x0001, 0xFF);
+        cpu.set_register(Register::HL, 0x2400);
+        cpu.set_register(Register::SP, 0x23FE);
+
+        assert_eq!(format_trace_line(&cpu, 1234),
+            "0000  06 FF     MVI B,0FFH    A:00 F:01 B:00 C:00 D:00 E:00 H:24 L:00 SP:23FE CYC:1234");
+    }
+
+    #[test]
+    fn test_filter() {
+        let output = SharedOutput::default();
+        let filter = TraceFilter { pc_range: Some((0x0002, 0xFFFF)), skip: 1, limit: Some(2) };
+        let mut tracer = Tracer::new(Box::new(output.clone()), filter);
+        let mut cpu = Cpu::new(Bus::new());
+
+        // Executing NOPs: the first is skipped, then the range leaves out 0001,
+        // and the limit stops after 0002 and 0003
+        for pc in 0..6 {
+            cpu.set_register(Register::PC, pc);
+            tracer.trace(&cpu, pc as u64 * 4);
+        }
+
+        let lines = trace_lines(&output);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("0002  00        NOP"));
+        assert!(lines[1].ends_with("CYC:12"));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::bus::*;
+use crate::constants::*;
+use crate::overlay::*;
+use crate::utils::*;
+
+// Each pixel is written to the framebuffer as 3 bytes (RGB24)
+const BYTES_PER_PIXEL: usize = 3;
+
+const PIXEL_OFF: Colour = [0x00, 0x00, 0x00];
+
+// Video RAM holds the screen at 1 bit per pixel as the hardware scans it out:
+// 224 lines of 32 bytes (256 pixels) each, least significant bit first. The
+// monitor is rotated 90 degrees counter-clockwise in the cabinet, so each of
+// those lines becomes a column of the upright picture, drawn from the bottom up.
+// Lit pixels take their colour from the overlay, unless the game has turned
+// the whole screen red.
+pub struct Video {
+    framebuffer: Vec<Byte>,
+    overlay: Overlay,
+}
+
+impl Video {
+
+    pub fn new(overlay: Overlay) -> Video {
+        Video {
+            framebuffer: vec![0; (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize * BYTES_PER_PIXEL],
+            overlay: overlay,
+        }
+    }
+
+    pub fn update(&mut self, bus: &Bus, screen_red: bool) {
+        let bytes_per_line = (DISPLAY_HEIGHT / 8) as usize;
+
+        for offset in 0..VRAM_SIZE {
+            let data = bus.peek(VRAM_START + offset as Word);
+
+            let x = offset / bytes_per_line;
+            let first_bit = (offset % bytes_per_line) * 8;
+
+            for bit in 0..8 {
+                let y = DISPLAY_HEIGHT as usize - 1 - (first_bit + bit);
+                let colour = match is_bit_set(&data, bit) {
+                    true if screen_red => RED,
+                    true => self.overlay.colour(x, y),
+                    false => PIXEL_OFF,
+                };
+
+                let pixel = (y * DISPLAY_WIDTH as usize + x) * BYTES_PER_PIXEL;
+                self.framebuffer[pixel..pixel + BYTES_PER_PIXEL].copy_from_slice(&colour);
+            }
+        }
+    }
+
+    pub fn framebuffer(&self) -> &[Byte] {
+        &self.framebuffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(video: &Video, x: usize, y: usize) -> &[Byte] {
+        let pixel = (y * DISPLAY_WIDTH as usize + x) * BYTES_PER_PIXEL;
+        &video.framebuffer()[pixel..pixel + BYTES_PER_PIXEL]
+    }
+
+    #[test]
+    fn test_update_rotates_screen() {
+        let mut bus = Bus::new();
+        let mut video = Video::new(Overlay::none());
+
+        // First bit of VRAM is the bottom left corner of the upright screen
+        bus.write_byte(VRAM_START, 0x01);
+        // Last bit of the first line is the top left corner
+        bus.write_byte(VRAM_START + 0x1F, 0x80);
+        // First bit of the last line is the bottom right corner
+        bus.write_byte(VRAM_START + 0x1BE0, 0x01);
+        // Bit 2 of the second byte in the second line
+        bus.write_byte(VRAM_START + 0x21, 0x04);
+
+        video.update(&bus, false);
+
+        assert_eq!(pixel(&video, 0, 255), WHITE);
+        assert_eq!(pixel(&video, 0, 0), WHITE);
+        assert_eq!(pixel(&video, 223, 255), WHITE);
+        assert_eq!(pixel(&video, 1, 255 - 10), WHITE);
+
+        let lit = video.framebuffer().iter().filter(|&&b| b != 0).count();
+        assert_eq!(lit, 4 * BYTES_PER_PIXEL);
+    }
+
+    #[test]
+    fn test_update_applies_overlay() {
+        let mut bus = Bus::new();
+        let mut video = Video::new(Overlay::from_bands(&CLASSIC_BANDS));
+
+        // A reserve ship under the green gel, and the top left corner
+        bus.write_byte(VRAM_START + 0x200, 0x01);
+        bus.write_byte(VRAM_START + 0x1F, 0x80);
+
+        video.update(&bus, false);
+
+        assert_eq!(pixel(&video, 16, 255), GREEN);
+        assert_eq!(pixel(&video, 0, 0), WHITE);
+        assert_eq!(pixel(&video, 17, 255), PIXEL_OFF);
+
+        video.update(&bus, true);
+        assert_eq!(pixel(&video, 16, 255), RED);
+        assert_eq!(pixel(&video, 0, 0), RED);
+        assert_eq!(pixel(&video, 17, 255), PIXEL_OFF);
+    }
+}
@@ -0,0 +1,56 @@
+// The board has a watchdog timer that resets the CPU unless the game writes
+// to it (OUT 6 on Space Invaders) often enough. The game kicks it every
+// frame, so it only fires when the program has got stuck
+pub struct Watchdog {
+    timeout: usize,
+    frames: usize,
+}
+
+impl Watchdog {
+
+    // The timeout is in frames. The hardware counts 255 vertical blanks
+    pub fn new(timeout: usize) -> Watchdog {
+        Watchdog {
+            timeout: timeout,
+            frames: 0,
+        }
+    }
+
+    pub fn kick(&mut self) {
+        self.frames = 0;
+    }
+
+    // Called once a frame, returning true when the timer runs out. It then
+    // starts counting again, as the reset game should soon be kicking it
+    pub fn tick(&mut self) -> bool {
+        self.frames += 1;
+
+        if self.frames >= self.timeout {
+            self.frames = 0;
+            return true;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog() {
+        let mut watchdog = Watchdog::new(3);
+
+        assert!(!watchdog.tick());
+        assert!(!watchdog.tick());
+        watchdog.kick();
+
+        assert!(!watchdog.tick());
+        assert!(!watchdog.tick());
+        assert!(watchdog.tick());
+
+        // Counting starts over after it fires
+        assert!(!watchdog.tick());
+    }
+}
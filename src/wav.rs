@@ -0,0 +1,231 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::constants::*;
+
+// Reads a PCM WAV file as 16-bit mono at SAMPLE_RATE, mixing down extra
+// channels and resampling as needed
+pub fn read_wav(path: &Path) -> Result<Vec<i16>, String> {
+    let data = fs::read(path)
+        .map_err(|e| format!("{} could not be read: {}", path.display(), e))?;
+
+    parse_wav(&data).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse_wav(data: &[u8]) -> Result<Vec<i16>, String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(String::from("not a WAV file"));
+    }
+
+    // The file is a list of chunks, each an id and a little endian length.
+    // Only the format and the samples matter here
+    let mut format = None;
+    let mut samples = None;
+    let mut offset = 12;
+
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let len = read_u32(data, offset + 4) as usize;
+        let body = &data[offset + 8..data.len().min(offset + 8 + len)];
+
+        match id {
+            b"fmt " if body.len() >= 16 => format = Some(body),
+            b"data" => samples = Some(body),
+            _ => (),
+        };
+
+        // Chunks are padded to an even length
+        offset += 8 + len + (len & 1);
+    }
+
+    let format = format.ok_or("missing format chunk")?;
+    let samples = samples.ok_or("missing data chunk")?;
+
+    let encoding = read_u16(format, 0);
+    let channels = read_u16(format, 2) as usize;
+    let rate = read_u32(format, 4) as usize;
+    let bits = read_u16(format, 14);
+
+    if encoding != 1 {
+        return Err(format!("unsupported encoding {}, expected PCM", encoding));
+    }
+
+    if channels == 0 || rate == 0 {
+        return Err(String::from("invalid format"));
+    }
+
+    let mono: Vec<i16> = match bits {
+        // 8-bit samples are unsigned, 16-bit ones signed
+        8 => samples.chunks_exact(channels)
+            .map(|frame| mix(frame.iter().map(|&s| ((s as i32) - 128) << 8)))
+            .collect(),
+        16 => samples.chunks_exact(channels * 2)
+            .map(|frame| mix(frame.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as i32)))
+            .collect(),
+        _ => return Err(format!("unsupported sample size of {} bits", bits)),
+    };
+
+    Ok(resample(&mono, rate))
+}
+
+// Streams 16-bit mono samples at SAMPLE_RATE to a PCM WAV file. The header
+// can only be completed once the length is known, so finish must be called
+pub struct WavWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+
+    pub fn create(path: &Path) -> Result<WavWriter, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("{} could not be created: {}", path.display(), e))?;
+
+        let mut writer = WavWriter {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            data_len: 0,
+        };
+
+        writer.write_bytes(&wav_header(0))?;
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, samples: &[i16]) -> Result<(), String> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+
+        self.write_bytes(&bytes)?;
+        self.data_len += bytes.len() as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let header = wav_header(self.data_len);
+
+        self.file.seek(SeekFrom::Start(0))
+            .map_err(|e| format!("{} could not be written: {}", self.path.display(), e))?;
+        self.write_bytes(&header)?;
+
+        self.file.flush()
+            .map_err(|e| format!("{} could not be written: {}", self.path.display(), e))
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.file.write_all(bytes)
+            .map_err(|e| format!("{} could not be written: {}", self.path.display(), e))
+    }
+}
+
+fn wav_header(data_len: u32) -> [u8; 44] {
+    let mut header = [0; 44];
+
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(36 + data_len).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&1u16.to_le_bytes());                        // PCM
+    header[22..24].copy_from_slice(&1u16.to_le_bytes());                        // Mono
+    header[24..28].copy_from_slice(&(SAMPLE_RATE as u32).to_le_bytes());
+    header[28..32].copy_from_slice(&(SAMPLE_RATE as u32 * 2).to_le_bytes());    // Bytes per second
+    header[32..34].copy_from_slice(&2u16.to_le_bytes());                        // Bytes per sample
+    header[34..36].copy_from_slice(&16u16.to_le_bytes());                       // Bits per sample
+
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_len.to_le_bytes());
+
+    header
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn mix(channels: impl Iterator<Item = i32>) -> i16 {
+    let (sum, count) = channels.fold((0, 0), |(sum, count), s| (sum + s, count + 1));
+    (sum / count) as i16
+}
+
+fn resample(samples: &[i16], rate: usize) -> Vec<i16> {
+    if rate == SAMPLE_RATE || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    // Linear interpolation between the two nearest source samples
+    let len = samples.len() * SAMPLE_RATE / rate;
+
+    (0..len).map(|i| {
+        let position = i * rate;
+        let index = position / SAMPLE_RATE;
+        let fraction = (position % SAMPLE_RATE) as i32;
+
+        let a = samples[index] as i32;
+        let b = samples[(index + 1).min(samples.len() - 1)] as i32;
+
+        (a + (b - a) * fraction / SAMPLE_RATE as i32) as i16
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_wav(samples: &[i16]) -> Vec<u8> {
+        let mut wav = wav_header(samples.len() as u32 * 2).to_vec();
+        for sample in samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        wav
+    }
+
+    #[test]
+    fn test_parse_wav() {
+        let samples = [0, 1000, -1000, i16::MAX, i16::MIN];
+        assert_eq!(parse_wav(&encode_wav(&samples)).unwrap(), samples);
+    }
+
+    #[test]
+    fn test_convert() {
+        // 8-bit stereo at half the sample rate
+        let mut wav = encode_wav(&[]);
+        wav[22] = 2;
+        wav[24..28].copy_from_slice(&(SAMPLE_RATE as u32 / 2).to_le_bytes());
+        wav[34] = 8;
+        wav[40..44].copy_from_slice(&4u32.to_le_bytes());
+        wav.extend_from_slice(&[0x80, 0x80, 0xC0, 0xC0]);
+
+        assert_eq!(parse_wav(&wav).unwrap(), [0, 0x2000, 0x4000, 0x4000]);
+    }
+
+    #[test]
+    fn test_wav_writer() {
+        let path = std::env::temp_dir().join(format!("space-invaders-wav-{}.wav", std::process::id()));
+
+        let mut writer = WavWriter::create(&path).unwrap();
+        writer.write(&[1, 2, 3]).unwrap();
+        writer.write(&[-4]).unwrap();
+        writer.finish().unwrap();
+
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(data, encode_wav(&[1, 2, 3, -4]));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse_wav(b"not a wav file").is_err());
+
+        let mut wav = encode_wav(&[0]);
+        wav[34] = 24;
+        assert!(parse_wav(&wav).is_err());
+    }
+}